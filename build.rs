@@ -0,0 +1,29 @@
+// Detects which platform facilities are available, so that the source can
+// refer to a facility rather than to the list of platforms providing it.
+//
+// * `has_so_peercred`: peer credentials via `SO_PEERCRED`.
+// * `has_local_peercred`: peer credentials via `LOCAL_PEERCRED`.
+// * `has_getpeereid`: peer credentials via `getpeereid`.
+// * `has_accept4`: `accept4` with `SOCK_CLOEXEC` and `SOCK_NONBLOCK`.
+// * `has_sock_cloexec`: `SOCK_CLOEXEC` in the type passed to `socket`.
+
+use std::env;
+
+const CFGS: &[(&str, &[&str])] = &[
+    ("has_so_peercred", &["linux", "android"]),
+    ("has_local_peercred", &["macos", "ios", "freebsd"]),
+    ("has_getpeereid", &["dragonfly", "openbsd", "netbsd"]),
+    ("has_accept4", &["linux", "freebsd", "dragonfly", "netbsd", "openbsd"]),
+    ("has_sock_cloexec", &["linux", "android", "freebsd", "dragonfly", "netbsd", "openbsd"]),
+];
+
+fn main() {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+
+    for &(cfg, platforms) in CFGS {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+        if platforms.contains(&&*target_os) {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+}
@@ -6,36 +6,33 @@
 
 extern crate debug_builders;
 extern crate libc;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 use debug_builders::DebugStruct;
 use std::ascii;
-use std::convert::AsRef;
+use std::borrow::Borrow;
+use std::convert::{AsRef, TryFrom};
 use std::cmp::{self, Ordering};
 use std::ffi::OsStr;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::net::Shutdown;
+use std::ops::Deref;
 use std::iter::IntoIterator;
 use std::mem;
 use std::os::unix::io::{RawFd, AsRawFd};
 use std::os::unix::ffi::OsStrExt;
 use std::fmt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize};
+use std::str::FromStr;
 
-extern "C" {
-    fn socketpair(domain: libc::c_int,
-                  ty: libc::c_int,
-                  proto: libc::c_int,
-                  sv: *mut [libc::c_int; 2])
-                  -> libc::c_int;
-
-    #[cfg(feature = "socket_timeout")]
-    fn getsockopt(socket: libc::c_int,
-                  level: libc::c_int,
-                  option_name: libc::c_int,
-                  option_value: *mut libc::c_void,
-                  option_len: *mut libc::c_void)
-                  -> libc::c_int;
-}
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod linux;
 
 fn sun_path_offset() -> usize {
     unsafe {
@@ -47,6 +44,25 @@ fn sun_path_offset() -> usize {
     }
 }
 
+/// The size of the `sun_path` field of `sockaddr_un` on this platform.
+///
+/// This bounds the length of socket addresses: 108 bytes on Linux, and 104
+/// on macOS and the BSDs. Pathname addresses must be strictly shorter, to
+/// leave room for a null terminator, while abstract addresses, including
+/// their leading null byte, may use all of it.
+pub const SUN_PATH_MAX: usize = unsafe {
+    // Work with an actual instance of the type, as in sun_path_offset
+    mem::MaybeUninit::<libc::sockaddr_un>::zeroed().assume_init().sun_path.len()
+};
+
+/// Returns the size of the `sun_path` field of `sockaddr_un` on this
+/// platform.
+///
+/// This is equal to `SUN_PATH_MAX`.
+pub fn sun_path_max() -> usize {
+    SUN_PATH_MAX
+}
+
 fn cvt(v: libc::c_int) -> io::Result<libc::c_int> {
     if v < 0 {
         Err(io::Error::last_os_error())
@@ -55,6 +71,16 @@ fn cvt(v: libc::c_int) -> io::Result<libc::c_int> {
     }
 }
 
+fn cvt_r<F>(mut f: F) -> io::Result<libc::c_int>
+        where F: FnMut() -> libc::c_int {
+    loop {
+        match cvt(f()) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            other => return other,
+        }
+    }
+}
+
 fn cvt_s(v: libc::ssize_t) -> io::Result<libc::ssize_t> {
     if v < 0 {
         Err(io::Error::last_os_error())
@@ -63,6 +89,29 @@ fn cvt_s(v: libc::ssize_t) -> io::Result<libc::ssize_t> {
     }
 }
 
+// Received file descriptors should not leak into child processes
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+const RECV_CLOEXEC: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+const RECV_CLOEXEC: libc::c_int = 0;
+
+// Writes to a closed peer should report EPIPE rather than raise SIGPIPE.
+// Platforms without MSG_NOSIGNAL set SO_NOSIGPIPE on each socket instead.
+#[cfg(any(target_os = "linux",
+          target_os = "android",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "netbsd",
+          target_os = "openbsd"))]
+const SEND_NOSIGNAL: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux",
+              target_os = "android",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd",
+              target_os = "openbsd")))]
+const SEND_NOSIGNAL: libc::c_int = 0;
+
 struct Inner(RawFd);
 
 impl Drop for Inner {
@@ -75,17 +124,86 @@ impl Drop for Inner {
 
 impl Inner {
     fn new(kind: libc::c_int) -> io::Result<Inner> {
+        Inner::with_cloexec(kind, false)
+    }
+
+    #[cfg(has_sock_cloexec)]
+    fn with_cloexec(kind: libc::c_int, cloexec: bool) -> io::Result<Inner> {
+        let kind = if cloexec { kind | libc::SOCK_CLOEXEC } else { kind };
         unsafe {
             cvt(libc::socket(libc::AF_UNIX, kind, 0)).map(Inner)
         }
     }
 
-    fn new_pair() -> io::Result<(Inner, Inner)> {
+    // No SOCK_CLOEXEC here, so there is a window in which another thread's
+    // exec can inherit the descriptor.
+    #[cfg(not(has_sock_cloexec))]
+    fn with_cloexec(kind: libc::c_int, cloexec: bool) -> io::Result<Inner> {
+        unsafe {
+            let inner = try!(cvt(libc::socket(libc::AF_UNIX, kind, 0)).map(Inner));
+            if cloexec {
+                try!(cvt(libc::fcntl(inner.0, libc::F_SETFD, libc::FD_CLOEXEC)));
+            }
+            try!(inner.suppress_sigpipe());
+            Ok(inner)
+        }
+    }
+
+    fn new_pair(kind: libc::c_int) -> io::Result<(Inner, Inner)> {
         unsafe {
             let mut fds = [0, 0];
-            try!(cvt(socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, &mut fds)));
-            Ok((Inner(fds[0]), Inner(fds[1])))
+            try!(cvt(libc::socketpair(libc::AF_UNIX, kind, 0, fds.as_mut_ptr())));
+            let (a, b) = (Inner(fds[0]), Inner(fds[1]));
+            try!(a.suppress_sigpipe());
+            try!(b.suppress_sigpipe());
+            Ok((a, b))
+        }
+    }
+
+    // See SEND_NOSIGNAL
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn suppress_sigpipe(&self) -> io::Result<()> {
+        self.setsockopt(libc::SOL_SOCKET, libc::SO_NOSIGPIPE, 1 as libc::c_int)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    fn suppress_sigpipe(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Accepted sockets always have the close-on-exec flag set
+    #[cfg(has_accept4)]
+    unsafe fn accept(&self,
+                     addr: *mut libc::sockaddr,
+                     len: *mut libc::socklen_t,
+                     nonblocking: bool)
+                     -> io::Result<Inner> {
+        let mut flags = libc::SOCK_CLOEXEC;
+        if nonblocking {
+            flags |= libc::SOCK_NONBLOCK;
+        }
+        cvt_r(|| libc::accept4(self.0, addr, len, flags)).map(Inner)
+    }
+
+    #[cfg(not(has_accept4))]
+    unsafe fn accept(&self,
+                     addr: *mut libc::sockaddr,
+                     len: *mut libc::socklen_t,
+                     nonblocking: bool)
+                     -> io::Result<Inner> {
+        let inner = try!(cvt_r(|| libc::accept(self.0, addr, len)).map(Inner));
+        try!(cvt(libc::fcntl(inner.0, libc::F_SETFD, libc::FD_CLOEXEC)));
+        try!(inner.suppress_sigpipe());
+        if nonblocking {
+            try!(inner.set_nonblocking(true));
         }
+        Ok(inner)
+    }
+
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.0;
+        mem::forget(self);
+        fd
     }
 
     fn try_clone(&self) -> io::Result<Inner> {
@@ -94,6 +212,36 @@ impl Inner {
         }
     }
 
+    #[cfg(any(target_os = "linux",
+              target_os = "android",
+              target_os = "macos",
+              target_os = "ios",
+              target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "netbsd",
+              target_os = "openbsd"))]
+    fn try_clone_cloexec(&self) -> io::Result<Inner> {
+        unsafe {
+            cvt(libc::fcntl(self.0, libc::F_DUPFD_CLOEXEC, 0)).map(Inner)
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux",
+                  target_os = "android",
+                  target_os = "macos",
+                  target_os = "ios",
+                  target_os = "freebsd",
+                  target_os = "dragonfly",
+                  target_os = "netbsd",
+                  target_os = "openbsd")))]
+    fn try_clone_cloexec(&self) -> io::Result<Inner> {
+        let inner = try!(self.try_clone());
+        unsafe {
+            try!(cvt(libc::fcntl(inner.0, libc::F_SETFD, libc::FD_CLOEXEC)));
+        }
+        Ok(inner)
+    }
+
     fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         let how = match how {
             Shutdown::Read => libc::SHUT_RD,
@@ -106,16 +254,406 @@ impl Inner {
         }
     }
 
-    #[cfg(feature = "socket_timeout")]
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        unsafe {
+            let flags = try!(cvt(libc::fcntl(self.0, libc::F_GETFL)));
+            let flags = if nonblocking {
+                flags | libc::O_NONBLOCK
+            } else {
+                flags & !libc::O_NONBLOCK
+            };
+            cvt(libc::fcntl(self.0, libc::F_SETFL, flags)).map(|_| ())
+        }
+    }
+
+    fn nonblocking(&self) -> io::Result<bool> {
+        unsafe {
+            let flags = try!(cvt(libc::fcntl(self.0, libc::F_GETFL)));
+            Ok(flags & libc::O_NONBLOCK != 0)
+        }
+    }
+
+    fn setsockopt<T>(&self, level: libc::c_int, name: libc::c_int, value: T) -> io::Result<()> {
+        unsafe {
+            cvt_r(|| {
+                libc::setsockopt(self.0,
+                                 level,
+                                 name,
+                                 &value as *const _ as *const _,
+                                 mem::size_of::<T>() as libc::socklen_t)
+            })
+                .map(|_| ())
+        }
+    }
+
+    fn getsockopt<T: Copy>(&self, level: libc::c_int, name: libc::c_int) -> io::Result<T> {
+        unsafe {
+            let mut value: T = mem::zeroed();
+            let mut size = mem::size_of::<T>() as libc::socklen_t;
+            try!(cvt_r(|| {
+                libc::getsockopt(self.0, level, name, &mut value as *mut _ as *mut _, &mut size)
+            }));
+            Ok(value)
+        }
+    }
+
+    fn set_buffer_size(&self, kind: libc::c_int, size: usize) -> io::Result<()> {
+        let size = cmp::min(size, libc::c_int::max_value() as usize) as libc::c_int;
+        self.setsockopt(libc::SOL_SOCKET, kind, size)
+    }
+
+    fn buffer_size(&self, kind: libc::c_int) -> io::Result<usize> {
+        let size: libc::c_int = try!(self.getsockopt(libc::SOL_SOCKET, kind));
+        Ok(size as usize)
+    }
+
+    fn available_bytes(&self) -> io::Result<usize> {
+        unsafe {
+            let mut count: libc::c_int = 0;
+            try!(cvt(libc::ioctl(self.0, libc::FIONREAD, &mut count)));
+            Ok(count as usize)
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn outgoing_queued_bytes(&self) -> io::Result<usize> {
+        unsafe {
+            let mut count: libc::c_int = 0;
+            try!(cvt(libc::ioctl(self.0, libc::TIOCOUTQ, &mut count)));
+            Ok(count as usize)
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_passcred(&self, passcred: bool) -> io::Result<()> {
+        self.setsockopt(libc::SOL_SOCKET, libc::SO_PASSCRED, passcred as libc::c_int)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn passcred(&self) -> io::Result<bool> {
+        let passcred: libc::c_int = try!(self.getsockopt(libc::SOL_SOCKET, libc::SO_PASSCRED));
+        Ok(passcred != 0)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_mark(&self, mark: u32) -> io::Result<()> {
+        self.setsockopt(libc::SOL_SOCKET, libc::SO_MARK, mark as libc::c_int)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn mark(&self) -> io::Result<u32> {
+        let mark: libc::c_int = try!(self.getsockopt(libc::SOL_SOCKET, libc::SO_MARK));
+        Ok(mark as u32)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_priority(&self, priority: i32) -> io::Result<()> {
+        self.setsockopt(libc::SOL_SOCKET, libc::SO_PRIORITY, priority as libc::c_int)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn priority(&self) -> io::Result<i32> {
+        let priority: libc::c_int = try!(self.getsockopt(libc::SOL_SOCKET, libc::SO_PRIORITY));
+        Ok(priority as i32)
+    }
+
+    // connect(2) can't simply be repeated after EINTR: BSD and macOS carry on
+    // connecting in the background and fail a second call with EALREADY or
+    // EISCONN. Instead wait for the attempt to finish and collect its result
+    // from SO_ERROR. Linux abandons the attempt, leaving the socket
+    // unconnected, in which case it is safe to try again.
+    unsafe fn connect(&self,
+                      addr: *const libc::sockaddr,
+                      len: libc::socklen_t)
+                      -> io::Result<()> {
+        loop {
+            if libc::connect(self.0, addr, len) == 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: self.0,
+                events: libc::POLLOUT,
+                revents: 0,
+            };
+            try!(cvt_r(|| libc::poll(&mut pollfd, 1, -1)));
+            if let Some(err) = try!(self.take_error()) {
+                return Err(err);
+            }
+            match SocketAddr::new(|addr, len| libc::getpeername(self.0, addr, len)) {
+                Ok(_) => return Ok(()),
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOTCONN) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        let errno: libc::c_int = try!(self.getsockopt(libc::SOL_SOCKET, libc::SO_ERROR));
+
+        if errno == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(io::Error::from_raw_os_error(errno)))
+        }
+    }
+
+    #[cfg(has_so_peercred)]
+    fn peer_cred(&self) -> io::Result<UCred> {
+        let cred: libc::ucred = try!(self.getsockopt(libc::SOL_SOCKET, libc::SO_PEERCRED));
+        Ok(UCred {
+            uid: cred.uid,
+            gid: cred.gid,
+            pid: cred.pid,
+        })
+    }
+
+    #[cfg(has_local_peercred)]
+    fn peer_cred(&self) -> io::Result<UCred> {
+        let cred: libc::xucred = try!(self.getsockopt(libc::SOL_LOCAL, libc::LOCAL_PEERCRED));
+        if cred.cr_version != libc::XUCRED_VERSION || cred.cr_ngroups < 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "unsupported LOCAL_PEERCRED credentials"));
+        }
+        Ok(UCred {
+            uid: cred.cr_uid,
+            // the first group is the effective group ID
+            gid: cred.cr_groups[0],
+            pid: -1,
+        })
+    }
+
+    #[cfg(has_getpeereid)]
+    fn peer_cred(&self) -> io::Result<UCred> {
+        unsafe {
+            let mut uid = 0;
+            let mut gid = 0;
+            try!(cvt(libc::getpeereid(self.0, &mut uid, &mut gid)));
+            Ok(UCred {
+                uid: uid,
+                gid: gid,
+                pid: -1,
+            })
+        }
+    }
+
+    #[cfg(not(any(has_so_peercred, has_local_peercred, has_getpeereid)))]
+    fn peer_cred(&self) -> io::Result<UCred> {
+        Err(io::Error::new(io::ErrorKind::Unsupported,
+                           "peer credentials are not supported on this platform"))
+    }
+
+    fn recv(&self, buf: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+        unsafe {
+            let count = try!(cvt_s(libc::recv(self.0,
+                                              buf.as_mut_ptr() as *mut _,
+                                              calc_len(buf),
+                                              flags)));
+            Ok(count as usize)
+        }
+    }
+
+    fn send(&self, buf: &[u8], flags: libc::c_int) -> io::Result<usize> {
+        unsafe {
+            let count = try!(cvt_s(libc::send(self.0,
+                                              buf.as_ptr() as *const _,
+                                              calc_len(buf),
+                                              flags | SEND_NOSIGNAL)));
+            Ok(count as usize)
+        }
+    }
+
+    fn send_to(&self,
+               buf: &[u8],
+               addr: &libc::sockaddr_un,
+               len: libc::socklen_t,
+               flags: libc::c_int)
+               -> io::Result<usize> {
+        unsafe {
+            let count = try!(cvt_s(libc::sendto(self.0,
+                                                buf.as_ptr() as *const _,
+                                                calc_len(buf),
+                                                flags | SEND_NOSIGNAL,
+                                                addr as *const _ as *const _,
+                                                len)));
+            Ok(count as usize)
+        }
+    }
+
+    fn recv_from(&self, buf: &mut [u8], flags: libc::c_int) -> io::Result<(usize, SocketAddr)> {
+        let mut count = 0;
+        let addr = try!(SocketAddr::new(|addr, len| {
+            unsafe {
+                count = libc::recvfrom(self.0,
+                                       buf.as_mut_ptr() as *mut _,
+                                       calc_len(buf),
+                                       flags,
+                                       addr,
+                                       len);
+                if count > 0 { 1 } else if count == 0 { 0 } else { -1 }
+            }
+        }));
+
+        Ok((count as usize, addr))
+    }
+
+    // IoSlice and IoSliceMut are guaranteed to be ABI compatible with iovec
+    fn send_msg(&self, bufs: &[io::IoSlice], ancillary: &AncillaryData) -> io::Result<usize> {
+        self.send_msg_to(bufs, None, ancillary, 0)
+    }
+
+    fn send_msg_to(&self,
+                   bufs: &[io::IoSlice],
+                   addr: Option<(&libc::sockaddr_un, libc::socklen_t)>,
+                   ancillary: &AncillaryData,
+                   flags: libc::c_int)
+                   -> io::Result<usize> {
+        unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            if let Some((addr, len)) = addr {
+                msg.msg_name = addr as *const _ as *mut _;
+                msg.msg_namelen = len;
+            }
+            msg.msg_iov = bufs.as_ptr() as *mut _;
+            msg.msg_iovlen = calc_iovlen(bufs.len()) as _;
+            if ancillary.len > 0 {
+                msg.msg_control = ancillary.buf.as_ptr() as *mut _;
+                msg.msg_controllen = ancillary.len as _;
+            }
+
+            let count = try!(cvt_s(libc::sendmsg(self.0, &msg, flags | SEND_NOSIGNAL)));
+            Ok(count as usize)
+        }
+    }
+
+    fn recv_msg(&self,
+                bufs: &mut [io::IoSliceMut],
+                ancillary: &mut AncillaryMessage)
+                -> io::Result<usize> {
+        self.recv_msg_from(bufs, None, ancillary, 0).map(|(count, _)| count)
+    }
+
+    /// Receives a message, storing its source address in `addr` if one is
+    /// given, and returns its length along with the flags reported in
+    /// `msg_flags`.
+    fn recv_msg_from(&self,
+                     bufs: &mut [io::IoSliceMut],
+                     addr: Option<&mut SocketAddr>,
+                     ancillary: &mut AncillaryMessage,
+                     flags: libc::c_int)
+                     -> io::Result<(usize, libc::c_int)> {
+        unsafe {
+            let mut name: libc::sockaddr_un = mem::zeroed();
+            let mut msg: libc::msghdr = mem::zeroed();
+            if addr.is_some() {
+                msg.msg_name = &mut name as *mut _ as *mut _;
+                msg.msg_namelen = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+            }
+            msg.msg_iov = bufs.as_mut_ptr() as *mut _;
+            msg.msg_iovlen = calc_iovlen(bufs.len()) as _;
+            if ancillary.capacity > 0 {
+                msg.msg_control = ancillary.buf.as_mut_ptr() as *mut _;
+                msg.msg_controllen = ancillary.capacity as _;
+            }
+
+            ancillary.clear();
+            let count = try!(cvt_s(libc::recvmsg(self.0, &mut msg, flags | RECV_CLOEXEC)));
+            ancillary.len = msg.msg_controllen as usize;
+            ancillary.truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+            if let Some(addr) = addr {
+                *addr = try!(SocketAddr::from_returned(name, msg.msg_namelen));
+            }
+            Ok((count as usize, msg.msg_flags))
+        }
+    }
+
+    /// Waits until one of `events` is signalled on the socket, returning a
+    /// `TimedOut` error if that doesn't happen within `timeout`.
+    fn poll(&self, events: libc::c_short, timeout: std::time::Duration) -> io::Result<()> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "operation timed out"));
+            }
+
+            let remaining = deadline - now;
+            let mut millis = remaining.as_secs()
+                                      .saturating_mul(1000)
+                                      .saturating_add(remaining.subsec_millis() as u64);
+            // round up so we don't spin on sub-millisecond remainders
+            if remaining.subsec_nanos() > remaining.subsec_millis() * 1000000 {
+                millis = millis.saturating_add(1);
+            }
+            let millis = cmp::min(millis, libc::c_int::max_value() as u64) as libc::c_int;
+
+            let mut pollfd = libc::pollfd {
+                fd: self.0,
+                events: events,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns whether a read from the socket would return without blocking.
+    fn is_readable(&self) -> io::Result<bool> {
+        self.ready(libc::POLLIN)
+    }
+
+    /// Returns whether a write to the socket would return without blocking.
+    fn is_writable(&self) -> io::Result<bool> {
+        self.ready(libc::POLLOUT)
+    }
+
+    /// Returns whether any of `events` is signalled on the socket, without
+    /// blocking.
+    ///
+    /// Hangups and errors are reported as ready, since the corresponding call
+    /// would return immediately.
+    fn ready(&self, events: libc::c_short) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.0,
+            events: events,
+            revents: 0,
+        };
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                _ => return Ok(pollfd.revents != 0),
+            }
+        }
+    }
+
     fn timeout(&self, kind: libc::c_int) -> io::Result<Option<std::time::Duration>> {
         let timeout = unsafe {
             let mut timeout: libc::timeval = mem::zeroed();
             let mut size = mem::size_of::<libc::timeval>() as libc::socklen_t;
-            try!(cvt(getsockopt(self.0,
-                                libc::SOL_SOCKET,
-                                kind,
-                                &mut timeout as *mut _ as *mut _,
-                                &mut size as *mut _ as *mut _)));
+            try!(cvt_r(|| {
+                libc::getsockopt(self.0,
+                                 libc::SOL_SOCKET,
+                                 kind,
+                                 &mut timeout as *mut _ as *mut _,
+                                 &mut size)
+            }));
             timeout
         };
 
@@ -127,23 +665,22 @@ impl Inner {
         }
     }
 
-    #[cfg(feature = "socket_timeout")]
     fn set_timeout(&self, dur: Option<std::time::Duration>, kind: libc::c_int) -> io::Result<()> {
         let timeout = match dur {
             Some(dur) => {
-                if dur.secs() == 0 && dur.extra_nanos() == 0 {
+                if dur.as_secs() == 0 && dur.subsec_nanos() == 0 {
                     return Err(io::Error::new(io::ErrorKind::InvalidInput,
                                               "cannot set a 0 duration timeout"));
                 }
 
-                let secs = if dur.secs() > libc::time_t::max_value() as u64 {
+                let secs = if dur.as_secs() > libc::time_t::max_value() as u64 {
                     libc::time_t::max_value()
                 } else {
-                    dur.secs() as libc::time_t
+                    dur.as_secs() as libc::time_t
                 };
                 let mut timeout = libc::timeval {
                     tv_sec: secs,
-                    tv_usec: (dur.extra_nanos() / 1000) as libc::suseconds_t,
+                    tv_usec: dur.subsec_micros() as libc::suseconds_t,
                 };
                 if timeout.tv_sec == 0 && timeout.tv_usec == 0 {
                     timeout.tv_usec = 1;
@@ -159,34 +696,141 @@ impl Inner {
         };
 
         unsafe {
-            cvt(libc::setsockopt(self.0,
+            cvt_r(|| {
+                libc::setsockopt(self.0,
                                  libc::SOL_SOCKET,
                                  kind,
                                  &timeout as *const _ as *const _,
-                                 mem::size_of::<libc::timeval>() as libc::socklen_t))
+                                 mem::size_of::<libc::timeval>() as libc::socklen_t)
+            })
                 .map(|_| ())
         }
     }
 }
 
+/// An error with more structure than a bare `io::Error`.
+///
+/// This is returned by the `_checked` variants of several methods, which
+/// distinguish invalid arguments and rejected peers from errors reported by
+/// the OS. It can be converted into an `io::Error` with `From`.
+#[derive(Debug)]
+pub enum UnixSocketError {
+    /// An error reported by the OS.
+    Io(io::Error),
+    /// A socket address was longer than the platform allows.
+    PathTooLong {
+        /// The length of the address in bytes.
+        len: usize,
+        /// The maximum length of the address in bytes.
+        max: usize,
+    },
+    /// An abstract address was malformed.
+    InvalidAbstractName(String),
+    /// The peer's credentials were rejected.
+    AuthenticationFailed(UCred),
+    /// A received frame was larger than the maximum frame size.
+    FrameTooLarge {
+        /// The size announced by the frame.
+        size: usize,
+        /// The maximum frame size.
+        max: usize,
+    },
+}
+
+impl fmt::Display for UnixSocketError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnixSocketError::Io(ref err) => fmt::Display::fmt(err, fmt),
+            UnixSocketError::PathTooLong { len, max } => {
+                write!(fmt, "path is {} bytes but the limit is {}", len, max)
+            }
+            UnixSocketError::InvalidAbstractName(ref msg) => {
+                write!(fmt, "invalid abstract path: {}", msg)
+            }
+            UnixSocketError::AuthenticationFailed(ref cred) => {
+                write!(fmt,
+                       "peer with uid {}, gid {} and pid {} was rejected",
+                       cred.uid,
+                       cred.gid,
+                       cred.pid)
+            }
+            UnixSocketError::FrameTooLarge { size, max } => {
+                write!(fmt, "frame is {} bytes but the limit is {}", size, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnixSocketError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            UnixSocketError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for UnixSocketError {
+    fn from(err: io::Error) -> UnixSocketError {
+        UnixSocketError::Io(err)
+    }
+}
+
+impl From<UnixSocketError> for io::Error {
+    fn from(err: UnixSocketError) -> io::Error {
+        let kind = match err {
+            UnixSocketError::Io(err) => return err,
+            UnixSocketError::PathTooLong { .. } |
+            UnixSocketError::InvalidAbstractName(_) => io::ErrorKind::InvalidInput,
+            UnixSocketError::AuthenticationFailed(_) => io::ErrorKind::PermissionDenied,
+            UnixSocketError::FrameTooLarge { .. } => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+fn check_path(path: &Path) -> Result<(), UnixSocketError> {
+    let bytes = path.as_os_str().as_bytes();
+    // Abstract paths don't need a null terminator
+    let max = if bytes.first() == Some(&0) {
+        SUN_PATH_MAX
+    } else {
+        SUN_PATH_MAX - 1
+    };
+    if bytes.len() > max {
+        Err(UnixSocketError::PathTooLong {
+            len: bytes.len(),
+            max: max,
+        })
+    } else {
+        Ok(())
+    }
+}
+
 unsafe fn sockaddr_un<P: AsRef<Path>>(path: P)
         -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
     let mut addr: libc::sockaddr_un = mem::zeroed();
     addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
 
-    let bytes = path.as_ref().as_os_str().as_bytes();
+    let path = path.as_ref();
+    let bytes = path.as_os_str().as_bytes();
 
-    match (bytes.get(0), bytes.len().cmp(&addr.sun_path.len())) {
-        // Abstract paths don't need a null terminator
-        (Some(&0), Ordering::Greater) => {
+    match check_path(path) {
+        Err(UnixSocketError::PathTooLong { len, max }) if bytes.get(0) == Some(&0) => {
             return Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                      "path must be no longer than SUN_LEN"))
+                                      format!("abstract path is {} bytes but the limit is {}",
+                                              len,
+                                              max)))
         }
-        (_, Ordering::Greater) | (_, Ordering::Equal) => {
+        Err(UnixSocketError::PathTooLong { len, max }) => {
             return Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                      "path must be shorter than SUN_LEN"));
+                                      format!("path '{}' is {} bytes but the limit is {}",
+                                              path.display(),
+                                              len,
+                                              max)));
         }
-        _ => {}
+        Err(err) => return Err(err.into()),
+        Ok(()) => {}
     }
     for (dst, src) in addr.sun_path.iter_mut().zip(bytes.iter()) {
         *dst = *src as libc::c_char;
@@ -202,7 +846,7 @@ unsafe fn sockaddr_un<P: AsRef<Path>>(path: P)
 }
 
 /// The kind of an address associated with a Unix socket.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AddressKind<'a> {
     /// An unnamed address.
     Unnamed,
@@ -214,6 +858,47 @@ pub enum AddressKind<'a> {
     Abstract(&'a [u8]),
 }
 
+impl<'a> AddressKind<'a> {
+    /// Returns `true` if this is an unnamed address.
+    pub fn is_unnamed(&self) -> bool {
+        matches!(*self, AddressKind::Unnamed)
+    }
+
+    /// Returns the path of a pathname address.
+    pub fn as_pathname(&self) -> Option<&'a Path> {
+        match *self {
+            AddressKind::Pathname(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of an abstract address.
+    pub fn as_abstract(&self) -> Option<&'a [u8]> {
+        match *self {
+            AddressKind::Abstract(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the address for display.
+///
+/// Pathname addresses are displayed as the path itself, abstract addresses
+/// as the name prefixed with `@` and with any non-printable bytes escaped,
+/// and unnamed addresses as `(unnamed)`.
+impl<'a> fmt::Display for AddressKind<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AddressKind::Unnamed => write!(fmt, "(unnamed)"),
+            AddressKind::Abstract(name) => {
+                try!(write!(fmt, "@"));
+                write_escaped(fmt, name)
+            }
+            AddressKind::Pathname(path) => write!(fmt, "{}", path.display()),
+        }
+    }
+}
+
 /// An address associated with a Unix socket.
 pub struct SocketAddr {
     addr: libc::sockaddr_un,
@@ -236,12 +921,39 @@ impl SocketAddr {
             let mut addr: libc::sockaddr_un = mem::zeroed();
             let mut len = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
             try!(cvt(f(&mut addr as *mut _ as *mut _, &mut len)));
+            SocketAddr::from_returned(addr, len)
+        }
+    }
 
-            if addr.sun_family != libc::AF_UNIX as libc::sa_family_t {
-                return Err(io::Error::new(io::ErrorKind::InvalidInput,
-                                          "file descriptor did not correspond to a Unix socket"));
-            }
+    // Checks an address filled in by a system call
+    fn from_returned(mut addr: libc::sockaddr_un,
+                     mut len: libc::socklen_t)
+                     -> io::Result<SocketAddr> {
+        // Linux reports a zero length address for datagrams sent from
+        // unbound sockets
+        if len == 0 {
+            addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+            len = sun_path_offset() as libc::socklen_t;
+        }
+
+        if addr.sun_family != libc::AF_UNIX as libc::sa_family_t {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "file descriptor did not correspond to a Unix socket"));
+        }
+
+        Ok(SocketAddr {
+            addr: addr,
+            len: len,
+        })
+    }
 
+    /// Creates an address corresponding to `path`.
+    ///
+    /// If `path` begins with a null byte, it will be interpreted as an
+    /// "abstract" address, like in `UnixListener::bind`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<SocketAddr> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
             Ok(SocketAddr {
                 addr: addr,
                 len: len,
@@ -249,10 +961,34 @@ impl SocketAddr {
         }
     }
 
-    /// Returns the value of the address.
-    pub fn address<'a>(&'a self) -> AddressKind<'a> {
-        let len = self.len as usize - sun_path_offset();
-        let path = unsafe { mem::transmute::<&[libc::c_char], &[u8]>(&self.addr.sun_path) };
+    /// Creates an address in the abstract namespace named by `name`.
+    ///
+    /// `name` should not include the leading null byte.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn from_abstract(name: &[u8]) -> io::Result<SocketAddr> {
+        let mut path = Vec::with_capacity(name.len() + 1);
+        path.push(0);
+        path.extend_from_slice(name);
+        SocketAddr::from_path(OsStr::from_bytes(&path))
+    }
+
+    /// Creates an unnamed address.
+    pub fn unnamed() -> SocketAddr {
+        unsafe {
+            let mut addr: libc::sockaddr_un = mem::zeroed();
+            addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+            SocketAddr {
+                addr: addr,
+                len: sun_path_offset() as libc::socklen_t,
+            }
+        }
+    }
+
+    /// Returns the value of the address.
+    pub fn address<'a>(&'a self) -> AddressKind<'a> {
+        let len = self.len as usize - sun_path_offset();
+        let path = unsafe { mem::transmute::<&[libc::c_char], &[u8]>(&self.addr.sun_path) };
 
         // OSX seems to return a len of 16 and a zeroed sun_path for unnamed addresses
         if len == 0 || (cfg!(not(target_os = "linux")) && self.addr.sun_path[0] == 0) {
@@ -263,683 +999,7829 @@ impl SocketAddr {
             AddressKind::Pathname(OsStr::from_bytes(&path[..len - 1]).as_ref())
         }
     }
-}
 
-impl fmt::Debug for SocketAddr {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match self.address() {
-            AddressKind::Unnamed => write!(fmt, "(unnamed)"),
-            AddressKind::Abstract(name) => write!(fmt, "{} (abstract)", AsciiEscaped(name)),
-            AddressKind::Pathname(path) => write!(fmt, "{:?} (pathname)", path)
-        }
+    /// Returns the path of a pathname address, or `None` for other kinds of
+    /// address.
+    pub fn as_os_str(&self) -> Option<&OsStr> {
+        self.address().as_pathname().map(Path::as_os_str)
     }
-}
-
-struct AsciiEscaped<'a>(&'a [u8]);
 
-impl<'a> fmt::Display for AsciiEscaped<'a> {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(fmt, "\""));
-        for byte in self.0.iter().cloned().flat_map(ascii::escape_default) {
-            try!(write!(fmt, "{}", byte as char));
-        }
-        write!(fmt, "\"")
+    /// Returns the name of an abstract address, without the leading null
+    /// byte, or `None` for other kinds of address.
+    pub fn as_abstract_bytes(&self) -> Option<&[u8]> {
+        self.address().as_abstract()
     }
-}
 
-/// A Unix stream socket.
-///
-/// # Examples
-///
-/// ```rust,no_run
-/// use unix_socket::UnixStream;
-/// use std::io::prelude::*;
-///
-/// let mut stream = UnixStream::connect("/path/to/my/socket").unwrap();
-/// stream.write_all(b"hello world").unwrap();
-/// let mut response = String::new();
-/// stream.read_to_string(&mut response).unwrap();
-/// println!("{}", response);
-/// ```
-pub struct UnixStream {
-    inner: Inner,
-}
-
-impl fmt::Debug for UnixStream {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = DebugStruct::new(fmt, "UnixStream")
-            .field("fd", &self.inner.0);
-        if let Ok(addr) = self.local_addr() {
-            builder = builder.field("local", &addr);
-        }
-        if let Ok(addr) = self.peer_addr() {
-            builder = builder.field("peer", &addr);
-        }
-        builder.finish()
+    /// Returns the address as it appears in `sun_path`.
+    ///
+    /// This includes the leading null byte of abstract addresses, and the
+    /// null terminator of pathname addresses if the OS reported one. The
+    /// slice is empty for unnamed addresses.
+    pub fn as_raw_bytes(&self) -> &[u8] {
+        let len = self.len as usize - sun_path_offset();
+        let path = unsafe { mem::transmute::<&[libc::c_char], &[u8]>(&self.addr.sun_path) };
+        &path[..len]
     }
-}
 
-impl UnixStream {
-    /// Connect to the socket named by `path`.
+    /// Returns the underlying `sockaddr_un`.
     ///
-    /// Linux provides, as a nonportable extension, a separate "abstract"
-    /// address namespace as opposed to filesystem-based addressing. If `path`
-    /// begins with a null byte, it will be interpreted as an "abstract"
-    /// address. Otherwise, it will be interpreted as a "pathname" address,
-    /// corresponding to a path on the filesystem.
-    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
-        unsafe {
-            let inner = try!(Inner::new(libc::SOCK_STREAM));
-            let (addr, len) = try!(sockaddr_un(path));
-
-            let ret = libc::connect(inner.0, &addr as *const _ as *const _, len);
-            if ret < 0 {
-                Err(io::Error::last_os_error())
-            } else {
-                Ok(UnixStream {
-                    inner: inner,
-                })
-            }
-        }
+    /// Only the first `raw_len` bytes of it are meaningful.
+    pub fn raw_sockaddr_un(&self) -> &libc::sockaddr_un {
+        &self.addr
     }
 
-    /// Create an unnamed pair of connected sockets.
-    ///
-    /// Returns two `UnixStream`s which are connected to each other.
-    pub fn unnamed() -> io::Result<(UnixStream, UnixStream)> {
-        let (i1, i2) = try!(Inner::new_pair());
-        Ok((UnixStream { inner: i1 }, UnixStream { inner: i2 }))
+    /// Returns the length of the underlying `sockaddr_un`, as passed to or
+    /// returned from system calls.
+    pub fn raw_len(&self) -> libc::socklen_t {
+        self.len
     }
 
-    /// Create a new independently owned handle to the underlying socket.
+    /// Creates an address from a raw `sockaddr_un` and its length, as
+    /// returned by `raw_sockaddr_un` and `raw_len`.
     ///
-    /// The returned `UnixStream` is a reference to the same stream that this
-    /// object references. Both handles will read and write the same stream of
-    /// data, and options set on one stream will be propogated to the other
-    /// stream.
-    pub fn try_clone(&self) -> io::Result<UnixStream> {
-        Ok(UnixStream {
-            inner: try!(self.inner.try_clone())
+    /// An `InvalidInput` error is returned if `addr` is not an `AF_UNIX`
+    /// address, or if `len` is out of range.
+    pub fn from_raw(addr: libc::sockaddr_un, len: libc::socklen_t) -> io::Result<SocketAddr> {
+        if addr.sun_family != libc::AF_UNIX as libc::sa_family_t {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "address is not an AF_UNIX address"));
+        }
+        let len_usize = len as usize;
+        if len_usize < sun_path_offset() || len_usize > mem::size_of::<libc::sockaddr_un>() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("invalid sockaddr_un length {}", len)));
+        }
+        Ok(SocketAddr {
+            addr: addr,
+            len: len,
         })
     }
 
-    /// Returns the socket address of the local half of this connection.
-    pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
-    }
-
-    /// Returns the socket address of the remote half of this connection.
-    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        SocketAddr::new(|addr, len| unsafe { libc::getpeername(self.inner.0, addr, len) })
+    /// Returns copies of the underlying `sockaddr_un` and its length, for
+    /// passing the address to foreign code.
+    pub fn to_raw_parts(&self) -> (libc::sockaddr_un, libc::socklen_t) {
+        (self.addr, self.len)
     }
 
-    /// Sets the read timeout for the socket.
-    ///
-    /// If the provided value is `None`, then `read` calls will block
-    /// indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
+    /// Creates an address from the parts returned by `to_raw_parts`.
     ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
-        self.inner.set_timeout(timeout, libc::SO_RCVTIMEO)
+    /// This validates its input in the same way as `from_raw`: an
+    /// `InvalidInput` error is returned if `addr.sun_family` is not
+    /// `AF_UNIX`, or if `len` is out of range.
+    pub fn from_raw_parts(addr: libc::sockaddr_un,
+                          len: libc::socklen_t)
+                          -> io::Result<SocketAddr> {
+        SocketAddr::from_raw(addr, len)
     }
 
-    /// Sets the write timeout for the socket.
-    ///
-    /// If the provided value is `None`, then `write` calls will block
-    /// indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
+    /// Returns the length in bytes of the path of a pathname address or the
+    /// name of an abstract address.
     ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
-        self.inner.set_timeout(timeout, libc::SO_SNDTIMEO)
+    /// Null bytes which are not part of the path or name are not counted, and
+    /// the length of an unnamed address is 0.
+    pub fn path_byte_len(&self) -> usize {
+        match self.address() {
+            AddressKind::Unnamed => 0,
+            AddressKind::Abstract(name) => name.len(),
+            AddressKind::Pathname(path) => path.as_os_str().len(),
+        }
     }
+}
 
-    /// Returns the read timeout of this socket.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn read_timeout(&self) -> io::Result<Option<std::time::Duration>> {
-        self.inner.timeout(libc::SO_RCVTIMEO)
+impl PartialEq for SocketAddr {
+    fn eq(&self, other: &SocketAddr) -> bool {
+        self.address() == other.address()
     }
+}
 
-    /// Returns the write timeout of this socket.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn write_timeout(&self) -> io::Result<Option<std::time::Duration>> {
-        self.inner.timeout(libc::SO_SNDTIMEO)
+impl Eq for SocketAddr {}
+
+impl Hash for SocketAddr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state)
     }
+}
 
-    /// Shut down the read, write, or both halves of this connection.
-    ///
-    /// This function will cause all pending and future I/O calls on the
-    /// specified portions to immediately return with an appropriate value
-    /// (see the documentation of `Shutdown`).
-    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        self.inner.shutdown(how)
+/// Unnamed addresses order before abstract addresses, which order before
+/// pathname addresses. Abstract names are compared bytewise and paths are
+/// compared like `Path`, component by component, which keeps the ordering
+/// consistent with equality.
+///
+/// The ordering is stable within a process, but the same addresses may order
+/// differently on other platforms.
+impl Ord for SocketAddr {
+    fn cmp(&self, other: &SocketAddr) -> Ordering {
+        fn rank(kind: &AddressKind) -> u8 {
+            match *kind {
+                AddressKind::Unnamed => 0,
+                AddressKind::Abstract(_) => 1,
+                AddressKind::Pathname(_) => 2,
+            }
+        }
+
+        let (a, b) = (self.address(), other.address());
+        match (a, b) {
+            (AddressKind::Abstract(a), AddressKind::Abstract(b)) => a.cmp(b),
+            (AddressKind::Pathname(a), AddressKind::Pathname(b)) => a.cmp(b),
+            _ => rank(&a).cmp(&rank(&b)),
+        }
     }
 }
 
-fn calc_len(buf: &[u8]) -> libc::size_t {
-    cmp::min(libc::size_t::max_value() as usize, buf.len()) as libc::size_t
+impl PartialOrd for SocketAddr {
+    fn partial_cmp(&self, other: &SocketAddr) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-impl io::Read for UnixStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        io::Read::read(&mut &*self, buf)
+impl fmt::Debug for SocketAddr {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.address() {
+            AddressKind::Unnamed => write!(fmt, "(unnamed)"),
+            AddressKind::Abstract(name) => write!(fmt, "{} (abstract)", AsciiEscaped(name)),
+            AddressKind::Pathname(path) => write!(fmt, "{:?} (pathname)", path)
+        }
     }
 }
 
-impl<'a> io::Read for &'a UnixStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        unsafe {
-            cvt_s(libc::recv(self.inner.0, buf.as_mut_ptr() as *mut _, calc_len(buf), 0))
-                .map(|r| r as usize)
+/// Fails with `InvalidInput` if the address cannot be represented, as is the
+/// case for a pathname which fills `sun_path` without a null terminator.
+impl TryFrom<std::os::unix::net::SocketAddr> for SocketAddr {
+    type Error = io::Error;
+
+    fn try_from(addr: std::os::unix::net::SocketAddr) -> io::Result<SocketAddr> {
+        if let Some(path) = addr.as_pathname() {
+            return SocketAddr::from_path(path);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+
+            if let Some(name) = addr.as_abstract_name() {
+                return SocketAddr::from_abstract(name);
+            }
+        }
+
+        #[cfg(target_os = "android")]
+        {
+            use std::os::android::net::SocketAddrExt;
+
+            if let Some(name) = addr.as_abstract_name() {
+                return SocketAddr::from_abstract(name);
+            }
         }
+
+        Ok(SocketAddr::unnamed())
     }
 }
 
-impl io::Write for UnixStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::Write::write(&mut &*self, buf)
+/// Equivalent to `SocketAddr::from_path`.
+impl TryFrom<PathBuf> for SocketAddr {
+    type Error = io::Error;
+
+    fn try_from(path: PathBuf) -> io::Result<SocketAddr> {
+        SocketAddr::from_path(path)
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        io::Write::flush(&mut &*self)
+/// Equivalent to `SocketAddr::from_path`.
+impl<'a> TryFrom<&'a Path> for SocketAddr {
+    type Error = io::Error;
+
+    fn try_from(path: &'a Path) -> io::Result<SocketAddr> {
+        SocketAddr::from_path(path)
     }
 }
 
-impl<'a> io::Write for &'a UnixStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        unsafe {
-            cvt_s(libc::send(self.inner.0, buf.as_ptr() as *const _, calc_len(buf), 0))
-                .map(|r| r as usize)
+/// Strings beginning with `@` are converted to abstract addresses with the
+/// `@` removed, and anything else to pathname addresses.
+///
+/// Unlike the `FromStr` implementation, no escape sequences are recognized,
+/// which makes this suited to addresses taken from configuration files.
+impl TryFrom<String> for SocketAddr {
+    type Error = io::Error;
+
+    fn try_from(s: String) -> io::Result<SocketAddr> {
+        SocketAddr::try_from(&*s)
+    }
+}
+
+/// Strings beginning with `@` are converted to abstract addresses with the
+/// `@` removed, and anything else to pathname addresses.
+///
+/// Unlike the `FromStr` implementation, no escape sequences are recognized,
+/// which makes this suited to addresses taken from configuration files.
+impl<'a> TryFrom<&'a str> for SocketAddr {
+    type Error = io::Error;
+
+    fn try_from(s: &'a str) -> io::Result<SocketAddr> {
+        match s.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract(name.as_bytes()),
+            None => SocketAddr::from_path(s),
         }
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+/// Formats the address for display in the same format as `AddressKind`.
+///
+/// The `FromStr` implementation parses this format.
+impl fmt::Display for SocketAddr {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.address(), fmt)
     }
 }
 
-impl AsRawFd for UnixStream {
-    fn as_raw_fd(&self) -> RawFd {
-        self.inner.0
+/// Parses an address in the format produced by the `Display`
+/// implementation.
+///
+/// Strings beginning with `@` are parsed as abstract addresses, `(unnamed)`
+/// as an unnamed address, and anything else as a pathname address.
+impl FromStr for SocketAddr {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<SocketAddr> {
+        if s.is_empty() {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "empty socket address"))
+        } else if s == "(unnamed)" {
+            Ok(SocketAddr::unnamed())
+        } else if let Some(name) = s.strip_prefix('@') {
+            SocketAddr::from_abstract(&try!(unescape(name)))
+        } else {
+            SocketAddr::from_path(s)
+        }
     }
 }
 
-#[cfg(feature = "from_raw_fd")]
-/// Requires the `from_raw_fd` feature.
-impl std::os::unix::io::FromRawFd for UnixStream {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
-        UnixStream {
-            inner: Inner(fd)
+fn unescape(s: &str) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid escape sequence");
+
+    let mut out = vec![];
+    let mut bytes = s.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
         }
+
+        let byte = match bytes.next() {
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b @ b'\\') | Some(b @ b'\'') | Some(b @ b'"') => b,
+            Some(b'x') => {
+                let hi = try!(bytes.next().and_then(|b| (b as char).to_digit(16)).ok_or_else(&invalid));
+                let lo = try!(bytes.next().and_then(|b| (b as char).to_digit(16)).ok_or_else(&invalid));
+                (hi * 16 + lo) as u8
+            }
+            _ => return Err(invalid()),
+        };
+        out.push(byte);
     }
+    Ok(out)
 }
 
-/// A structure representing a Unix domain socket server.
-///
-/// # Examples
-///
-/// ```rust,no_run
-/// use std::thread;
-/// use unix_socket::{UnixStream, UnixListener};
+fn write_escaped(fmt: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    for byte in bytes.iter().cloned().flat_map(ascii::escape_default) {
+        try!(write!(fmt, "{}", byte as char));
+    }
+    Ok(())
+}
+
+/// A byte slice which is displayed as a quoted, escaped string.
 ///
-/// fn handle_client(stream: UnixStream) {
-///     // ...
-/// }
+/// Each byte is escaped as by `std::ascii::escape_default`, so printable
+/// ASCII is displayed as is, except for quotes and backslashes, while
+/// control characters and bytes above 0x7f are displayed as escapes such as
+/// `\n` or `\x80`. The `Debug` and `Display` output is the same, and
+/// `to_string` is available through the `Display` implementation.
 ///
-/// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+/// # Examples
 ///
-/// // accept connections and process them, spawning a new thread for each one
-/// for stream in listener.incoming() {
-///     match stream {
-///         Ok(stream) => {
-///             /* connection succeeded */
-///             thread::spawn(|| handle_client(stream));
-///         }
-///         Err(err) => {
-///             /* connection failed */
-///             break;
-///         }
-///     }
-/// }
+/// ```rust
+/// use unix_socket::AsciiEscaped;
 ///
-/// // close the listener socket
-/// drop(listener);
+/// assert_eq!(AsciiEscaped::new(b"name\0\xff").to_string(), r#""name\x00\xff""#);
 /// ```
-pub struct UnixListener {
-    inner: Inner,
+#[derive(Clone, Copy)]
+pub struct AsciiEscaped<'a>(pub &'a [u8]);
+
+impl<'a> AsciiEscaped<'a> {
+    /// Wraps `bytes` for display.
+    pub fn new(bytes: &'a [u8]) -> AsciiEscaped<'a> {
+        AsciiEscaped(bytes)
+    }
 }
 
-impl fmt::Debug for UnixListener {
+impl<'a> fmt::Display for AsciiEscaped<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = DebugStruct::new(fmt, "UnixListener")
-            .field("fd", &self.inner.0);
-        if let Ok(addr) = self.local_addr() {
-            builder = builder.field("local", &addr);
-        }
-        builder.finish()
+        try!(write!(fmt, "\""));
+        try!(write_escaped(fmt, self.0));
+        write!(fmt, "\"")
     }
 }
 
-impl UnixListener {
-    /// Creates a new `UnixListener` which will be bound to the specified
-    /// socket.
-    ///
-    /// Linux provides, as a nonportable extension, a separate "abstract"
-    /// address namespace as opposed to filesystem-based addressing. If `path`
-    /// begins with a null byte, it will be interpreted as an "abstract"
-    /// address. Otherwise, it will be interpreted as a "pathname" address,
-    /// corresponding to a path on the filesystem.
-    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
-        unsafe {
-            let inner = try!(Inner::new(libc::SOCK_STREAM));
-            let (addr, len) = try!(sockaddr_un(path));
+impl<'a> fmt::Debug for AsciiEscaped<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
 
-            try!(cvt(libc::bind(inner.0, &addr as *const _ as *const _, len)));
-            try!(cvt(libc::listen(inner.0, 128)));
+/// Serializes the address as a map tagged with its kind.
+///
+/// Pathname addresses serialize as `{"type": "pathname", "path": "/some/path"}`,
+/// abstract addresses as `{"type": "abstract", "name": "<hex>"}` and unnamed
+/// addresses as `{"type": "unnamed"}`.
+///
+/// Requires the `serde` feature.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SocketAddr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        use serde::ser::{Error, SerializeStruct};
 
-            Ok(UnixListener {
-                inner: inner,
-            })
+        match self.address() {
+            AddressKind::Unnamed => {
+                let mut state = try!(serializer.serialize_struct("SocketAddr", 1));
+                try!(state.serialize_field("type", "unnamed"));
+                state.end()
+            }
+            AddressKind::Abstract(name) => {
+                let name = name.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+                let mut state = try!(serializer.serialize_struct("SocketAddr", 2));
+                try!(state.serialize_field("type", "abstract"));
+                try!(state.serialize_field("name", &name));
+                state.end()
+            }
+            AddressKind::Pathname(path) => {
+                let path = try!(path.to_str()
+                    .ok_or_else(|| S::Error::custom("path contains invalid UTF-8 characters")));
+                let mut state = try!(serializer.serialize_struct("SocketAddr", 2));
+                try!(state.serialize_field("type", "pathname"));
+                try!(state.serialize_field("path", path));
+                state.end()
+            }
         }
     }
+}
 
-    /// Accepts a new incoming connection to this listener.
-    pub fn accept(&self) -> io::Result<UnixStream> {
-        unsafe {
-            cvt(libc::accept(self.inner.0, 0 as *mut _, 0 as *mut _))
-                .map(|fd| UnixStream { inner: Inner(fd) })
-        }
+/// Deserializes an address from the representation produced by the
+/// `Serialize` implementation.
+///
+/// Requires the `serde` feature.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SocketAddr {
+    fn deserialize<D>(deserializer: D) -> Result<SocketAddr, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        deserializer.deserialize_struct("SocketAddr", FIELDS, SocketAddrVisitor)
     }
+}
 
-    /// Create a new independently owned handle to the underlying socket.
-    ///
-    /// The returned `UnixListener` is a reference to the same socket that this
-    /// object references. Both handles can be used to accept incoming
-    /// connections and options set on one listener will affect the other.
-    pub fn try_clone(&self) -> io::Result<UnixListener> {
-        Ok(UnixListener {
-            inner: try!(self.inner.try_clone())
-        })
-    }
+#[cfg(feature = "serde")]
+const FIELDS: &[&str] = &["type", "path", "name"];
 
-    /// Returns the socket address of the local half of this connection.
-    pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+#[cfg(feature = "serde")]
+struct SocketAddrVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for SocketAddrVisitor {
+    type Value = SocketAddr;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a Unix socket address")
     }
 
-    /// Returns an iterator over incoming connections.
-    ///
-    /// The iterator will never return `None`.
-    pub fn incoming<'a>(&'a self) -> Incoming<'a> {
-        Incoming {
-            listener: self
+    fn visit_map<A>(self, mut map: A) -> Result<SocketAddr, A::Error>
+        where A: serde::de::MapAccess<'de>
+    {
+        use serde::de::Error;
+
+        let mut kind: Option<String> = None;
+        let mut path: Option<String> = None;
+        let mut name: Option<String> = None;
+        while let Some(key) = try!(map.next_key::<String>()) {
+            let slot = match &*key {
+                "type" => &mut kind,
+                "path" => &mut path,
+                "name" => &mut name,
+                _ => return Err(A::Error::unknown_field(&key, FIELDS)),
+            };
+            if slot.is_some() {
+                return Err(A::Error::custom(format_args!("duplicate field `{}`", key)));
+            }
+            *slot = Some(try!(map.next_value()));
         }
+
+        let kind = try!(kind.ok_or_else(|| A::Error::missing_field("type")));
+        let addr = match &*kind {
+            "unnamed" => Ok(SocketAddr::unnamed()),
+            "abstract" => {
+                let name = try!(name.ok_or_else(|| A::Error::missing_field("name")));
+                let name = try!(unhex(&name).ok_or_else(|| {
+                    A::Error::invalid_value(serde::de::Unexpected::Str(&name), &"a hex string")
+                }));
+                SocketAddr::from_abstract(&name)
+            }
+            "pathname" => {
+                let path = try!(path.ok_or_else(|| A::Error::missing_field("path")));
+                SocketAddr::from_path(path)
+            }
+            _ => {
+                return Err(A::Error::unknown_variant(&kind, &["pathname", "abstract", "unnamed"]))
+            }
+        };
+        addr.map_err(A::Error::custom)
     }
 }
 
-impl AsRawFd for UnixListener {
-    fn as_raw_fd(&self) -> RawFd {
-        self.inner.0
-    }
+#[cfg(feature = "serde")]
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    let digits = s.chars().map(|c| c.to_digit(16)).collect::<Option<Vec<_>>>();
+    digits.and_then(|digits| {
+        digits.chunks(2)
+              .map(|d| if d.len() == 2 { Some((d[0] * 16 + d[1]) as u8) } else { None })
+              .collect()
+    })
 }
 
-#[cfg(feature = "from_raw_fd")]
-/// Requires the `from_raw_fd` feature.
-impl std::os::unix::io::FromRawFd for UnixListener {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
-        UnixListener {
-            inner: Inner(fd)
+/// A borrowed address in the Linux abstract socket namespace.
+///
+/// This is to `AbstractPathBuf` what `Path` is to `PathBuf`. The address is
+/// stored in its `sockaddr_un` form, with a leading null byte.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct AbstractPath([u8]);
+
+impl AbstractPath {
+    /// Wraps `name` as an abstract address.
+    ///
+    /// `name` must include the leading null byte which marks an abstract
+    /// address, and may not contain any other null bytes or be longer than
+    /// `SUN_PATH_MAX`.
+    pub fn new(name: &[u8]) -> io::Result<&AbstractPath> {
+        AbstractPath::new_checked(name).map_err(io::Error::from)
+    }
+
+    /// Like `new`, but reports errors as a `UnixSocketError`.
+    ///
+    /// A `PathTooLong` error is returned if `name` is too long, and an
+    /// `InvalidAbstractName` error if it is otherwise malformed.
+    pub fn new_checked(name: &[u8]) -> Result<&AbstractPath, UnixSocketError> {
+        if name.first() != Some(&0) {
+            let msg = "abstract path must begin with a null byte";
+            return Err(UnixSocketError::InvalidAbstractName(msg.to_owned()));
+        }
+        if name.len() > SUN_PATH_MAX {
+            return Err(UnixSocketError::PathTooLong {
+                len: name.len(),
+                max: SUN_PATH_MAX,
+            });
         }
+        if name[1..].contains(&0) {
+            let msg = "abstract path may not contain interior null bytes";
+            return Err(UnixSocketError::InvalidAbstractName(msg.to_owned()));
+        }
+        Ok(unsafe { &*(name as *const [u8] as *const AbstractPath) })
     }
-}
 
-impl<'a> IntoIterator for &'a UnixListener {
-    type Item = io::Result<UnixStream>;
-    type IntoIter = Incoming<'a>;
+    /// Checks that `name` is short enough to be used as an abstract name.
+    ///
+    /// `name` should not include the leading null byte, so it may be at most
+    /// `SUN_PATH_MAX - 1` bytes long. No other check is made: unlike C
+    /// strings, the kernel treats abstract names as arbitrary bytes, including
+    /// embedded nulls. `AbstractPath` itself is stricter and rejects interior
+    /// null bytes, which most tools cannot display or type.
+    pub fn validate(name: &[u8]) -> io::Result<()> {
+        let max = SUN_PATH_MAX - 1;
+        if name.len() > max {
+            return Err(UnixSocketError::PathTooLong {
+                len: name.len(),
+                max: max,
+            }.into());
+        }
+        Ok(())
+    }
 
-    fn into_iter(self) -> Incoming<'a> {
-        self.incoming()
+    /// Like `validate`, but additionally rejects any byte which is not
+    /// printable ASCII, including null bytes.
+    pub fn validate_printable(name: &[u8]) -> io::Result<()> {
+        try!(AbstractPath::validate(name));
+        if let Some(&byte) = name.iter().find(|&&b| !(0x20..=0x7e).contains(&b)) {
+            let msg = format!("abstract name contains non-printable byte {:#04x}", byte);
+            return Err(UnixSocketError::InvalidAbstractName(msg).into());
+        }
+        Ok(())
     }
-}
 
-/// An iterator over incoming connections to a `UnixListener`.
-///
-/// It will never return `None`.
-#[derive(Debug)]
-pub struct Incoming<'a> {
-    listener: &'a UnixListener,
-}
+    /// Returns the name of the address, without the leading null byte.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[1..]
+    }
 
-impl<'a> Iterator for Incoming<'a> {
-    type Item = io::Result<UnixStream>;
+    /// Returns the address as it appears in `sun_path`, including the leading
+    /// null byte.
+    pub fn as_sockaddr_bytes(&self) -> &[u8] {
+        &self.0
+    }
 
-    fn next(&mut self) -> Option<io::Result<UnixStream>> {
-        Some(self.listener.accept())
+    /// Returns an owned copy of the address.
+    pub fn to_abstract_path_buf(&self) -> AbstractPathBuf {
+        AbstractPathBuf(self.0.to_vec())
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (usize::max_value(), None)
+    fn sockaddr_un(&self) -> (libc::sockaddr_un, libc::socklen_t) {
+        unsafe {
+            let mut addr: libc::sockaddr_un = mem::zeroed();
+            addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+            for (dst, src) in addr.sun_path.iter_mut().zip(self.0.iter()) {
+                *dst = *src as libc::c_char;
+            }
+            (addr, (sun_path_offset() + self.0.len()) as libc::socklen_t)
+        }
     }
 }
 
-/// A Unix datagram socket.
-///
-/// # Examples
-///
-/// ```rust,no_run
-/// use unix_socket::UnixDatagram;
-///
-/// let socket = UnixDatagram::bind("/path/to/my/socket").unwrap();
-/// socket.send_to(b"hello world", "/path/to/other/socket").unwrap();
-/// let mut buf = [0; 100];
-/// let (count, address) = socket.recv_from(&mut buf).unwrap();
-/// println!("socket {:?} sent {:?}", address, &buf[..count]);
-/// ```
-pub struct UnixDatagram {
-    inner: Inner,
+impl fmt::Debug for AbstractPath {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", AsciiEscaped(self.as_bytes()))
+    }
 }
 
-impl fmt::Debug for UnixDatagram {
+impl fmt::Display for AbstractPath {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = DebugStruct::new(fmt, "UnixDatagram")
-            .field("fd", &self.inner.0);
-        if let Ok(addr) = self.local_addr() {
-            builder = builder.field("local", &addr);
-        }
-        builder.finish()
+        try!(write!(fmt, "@"));
+        write_escaped(fmt, self.as_bytes())
     }
 }
 
-impl UnixDatagram {
-    /// Creates a Unix datagram socket from the given path.
-    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
-        unsafe {
-            let inner = try!(Inner::new(libc::SOCK_DGRAM));
-            let (addr, len) = try!(sockaddr_un(path));
+impl AsRef<AbstractPath> for AbstractPath {
+    fn as_ref(&self) -> &AbstractPath {
+        self
+    }
+}
 
-            try!(cvt(libc::bind(inner.0, &addr as *const _ as *const _, len)));
+impl ToOwned for AbstractPath {
+    type Owned = AbstractPathBuf;
 
-            Ok(UnixDatagram {
-                inner: inner,
-            })
-        }
+    fn to_owned(&self) -> AbstractPathBuf {
+        self.to_abstract_path_buf()
     }
+}
 
-    /// Returns the address of this socket.
-    pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
-    }
+/// An owned address in the Linux abstract socket namespace.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AbstractPathBuf(Vec<u8>);
 
-    /// Receives data from the socket.
+impl AbstractPathBuf {
+    /// Creates an abstract address named by `name`.
     ///
-    /// On success, returns the number of bytes read and the address from
-    /// whence the data came.
-    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        let mut count = 0;
-        let addr = try!(SocketAddr::new(|addr, len| {
-            unsafe {
-                count = libc::recvfrom(self.inner.0,
-                                       buf.as_mut_ptr() as *mut _,
-                                       calc_len(buf),
-                                       0,
-                                       addr,
-                                       len);
-                if count > 0 { 1 } else if count == 0 { 0 } else { -1 }
-            }
-        }));
+    /// Unlike `AbstractPath::new`, `name` should not include the leading null
+    /// byte.
+    pub fn new(name: &[u8]) -> io::Result<AbstractPathBuf> {
+        let mut buf = Vec::with_capacity(name.len() + 1);
+        buf.push(0);
+        buf.extend_from_slice(name);
+        try!(AbstractPath::new(&buf));
+        Ok(AbstractPathBuf(buf))
+    }
 
-        Ok((count as usize, addr))
+    /// Returns the address as an `AbstractPath`.
+    pub fn as_abstract_path(&self) -> &AbstractPath {
+        self
     }
+}
 
-    /// Sends data on the socket to the given address.
-    ///
-    /// On success, returns the number of bytes written.
-    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
-        unsafe {
-            let (addr, len) = try!(sockaddr_un(path));
+impl Deref for AbstractPathBuf {
+    type Target = AbstractPath;
 
-            let count = try!(cvt_s(libc::sendto(self.inner.0,
-                                                buf.as_ptr() as *const _,
-                                                calc_len(buf),
-                                                0,
-                                                &addr as *const _ as *const _,
-                                                len)));
-            Ok(count as usize)
-        }
+    fn deref(&self) -> &AbstractPath {
+        unsafe { &*(&self.0[..] as *const [u8] as *const AbstractPath) }
     }
+}
 
-    /// Sets the read timeout for the socket.
-    ///
-    /// If the provided value is `None`, then `recv_from` calls will block
-    /// indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
-        self.inner.set_timeout(timeout, libc::SO_RCVTIMEO)
+impl AsRef<AbstractPath> for AbstractPathBuf {
+    fn as_ref(&self) -> &AbstractPath {
+        self
     }
+}
 
-    /// Sets the write timeout for the socket.
-    ///
-    /// If the provided value is `None`, then `send_to` calls will block
-    /// indefinitely. It is an error to pass the zero `Duration` to this
-    /// method.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
-        self.inner.set_timeout(timeout, libc::SO_SNDTIMEO)
+impl Borrow<AbstractPath> for AbstractPathBuf {
+    fn borrow(&self) -> &AbstractPath {
+        self
     }
+}
 
-    /// Returns the read timeout of this socket.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn read_timeout(&self) -> io::Result<Option<std::time::Duration>> {
-        self.inner.timeout(libc::SO_RCVTIMEO)
+/// Parses an address in the format produced by the `Display`
+/// implementation: `@` followed by the name, with escape sequences for
+/// bytes that are not printable ASCII.
+impl FromStr for AbstractPathBuf {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<AbstractPathBuf> {
+        match s.strip_prefix('@') {
+            Some(name) => AbstractPathBuf::new(&try!(unescape(name))),
+            None => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                   "abstract address must begin with '@'"))
+            }
+        }
     }
+}
 
-    /// Returns the write timeout of this socket.
-    ///
-    /// Requires the `socket_timeout` feature.
-    #[cfg(feature = "socket_timeout")]
-    pub fn write_timeout(&self) -> io::Result<Option<std::time::Duration>> {
-        self.inner.timeout(libc::SO_SNDTIMEO)
+impl fmt::Debug for AbstractPathBuf {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
     }
+}
 
-    /// Shut down the read, write, or both halves of this connection.
-    ///
-    /// This function will cause all pending and future I/O calls on the
-    /// specified portions to immediately return with an appropriate value
-    /// (see the documentation of `Shutdown`).
-    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        self.inner.shutdown(how)
+impl fmt::Display for AbstractPathBuf {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
     }
 }
 
-impl AsRawFd for UnixDatagram {
-    fn as_raw_fd(&self) -> RawFd {
-        self.inner.0
+/// Credentials of a process on the other end of a Unix socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UCred {
+    /// The user ID of the process.
+    pub uid: u32,
+    /// The group ID of the process.
+    pub gid: u32,
+    /// The process ID of the process, or `-1` if the platform does not
+    /// report it.
+    pub pid: i32,
+}
+
+fn timeout_until(deadline: std::time::Instant) -> std::time::Duration {
+    let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+    if timeout.as_secs() == 0 && timeout.subsec_nanos() < 1000 {
+        std::time::Duration::from_micros(1)
+    } else {
+        timeout
     }
 }
 
-#[cfg(feature = "from_raw_fd")]
-/// Requires the `from_raw_fd` feature.
-impl std::os::unix::io::FromRawFd for UnixDatagram {
-    unsafe fn from_raw_fd(fd: RawFd) -> UnixDatagram {
-        UnixDatagram {
-            inner: Inner(fd)
+fn check_id(kind: &str, id: u32, allowed: &[u32]) -> io::Result<()> {
+    if allowed.contains(&id) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                           format!("peer {} {} is not permitted", kind, id)))
+    }
+}
+
+fn rights_space(fds: usize) -> io::Result<usize> {
+    match fds.checked_mul(mem::size_of::<RawFd>()) {
+        Some(len) if len <= libc::c_uint::max_value() as usize => {
+            Ok(unsafe { libc::CMSG_SPACE(len as libc::c_uint) as usize })
         }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "too many file descriptors")),
     }
 }
 
-#[cfg(test)]
-mod test {
-    extern crate tempdir;
+/// Returns the number of bytes needed to hold a control message carrying
+/// `data_len` bytes of data, including its header and padding.
+///
+/// This is `CMSG_SPACE(data_len)`. It can be evaluated at compile time, so
+/// it may be used to size arrays.
+///
+/// Control message buffers must be aligned like a `cmsghdr`, which a byte
+/// array does not guarantee. Allocate them as `usize`s instead, as
+/// `AncillaryMessage` does.
+///
+/// # Panics
+///
+/// Panics if `data_len` does not fit in a `c_uint`.
+pub const fn cmsg_space(data_len: usize) -> usize {
+    if data_len > libc::c_uint::MAX as usize {
+        panic!("control message data is too large");
+    }
+    unsafe { libc::CMSG_SPACE(data_len as libc::c_uint) as usize }
+}
 
-    use std::thread;
-    use std::io;
+/// Returns the number of bytes needed to hold a control message carrying
+/// `$n` values of type `$ty`.
+///
+/// This expands to a constant expression when `$n` is a constant, so it can
+/// be used to size arrays on the stack. See `cmsg_space` for alignment
+/// requirements.
+///
+/// # Panics
+///
+/// Panics, or fails to compile when evaluated at compile time, if the size
+/// overflows.
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate unix_socket;
+///
+/// use std::os::unix::io::RawFd;
+///
+/// # fn main() {
+/// // room for a single SCM_RIGHTS message carrying 3 file descriptors
+/// let buf = [0usize; cmsg_space!(3, RawFd).div_ceil(std::mem::size_of::<usize>())];
+/// assert!(buf.len() * std::mem::size_of::<usize>() >= cmsg_space!(3, RawFd));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! cmsg_space {
+    ($n:expr, $ty:ty) => {
+        match (($n) as usize).checked_mul(::std::mem::size_of::<$ty>()) {
+            Some(len) => $crate::cmsg_space(len),
+            None => panic!("control message data is too large"),
+        }
+    }
+}
+
+/// Returns the number of bytes needed to hold an `SCM_RIGHTS` control
+/// message carrying `fds` file descriptors.
+///
+/// # Panics
+///
+/// Panics if the size overflows.
+pub fn cmsg_space_for_fds(fds: usize) -> usize {
+    cmsg_space!(fds, RawFd)
+}
+
+/// Returns the number of bytes needed to hold an `SCM_CREDENTIALS` control
+/// message.
+///
+/// Only supported on Linux.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn cmsg_space_for_creds() -> usize {
+    cmsg_space!(1, libc::ucred)
+}
+
+/// A builder for control messages to be sent along with data on a socket.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use std::os::unix::io::AsRawFd;
+/// use unix_socket::{AncillaryData, UnixStream};
+///
+/// let file = File::open("/etc/hosts").unwrap();
+/// let stream = UnixStream::connect("/path/to/my/socket").unwrap();
+///
+/// let mut ancillary = AncillaryData::new();
+/// ancillary.add_fds(&[file.as_raw_fd()]).unwrap();
+/// stream.send_with_ancillary(b"hello world", &ancillary).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AncillaryData {
+    buf: Vec<usize>,
+    len: usize,
+}
+
+impl AncillaryData {
+    /// Creates an empty set of control messages.
+    pub fn new() -> AncillaryData {
+        AncillaryData::default()
+    }
+
+    /// Adds an `SCM_RIGHTS` control message transferring the file
+    /// descriptors in `fds`.
+    ///
+    /// Ownership of the file descriptors is not transferred; they remain open
+    /// in this process.
+    pub fn add_fds(&mut self, fds: &[RawFd]) -> io::Result<&mut AncillaryData> {
+        if fds.is_empty() {
+            return Ok(self);
+        }
+
+        try!(rights_space(fds.len()));
+        let data = unsafe {
+            std::slice::from_raw_parts(fds.as_ptr() as *const u8, mem::size_of_val(fds))
+        };
+        Ok(self.push(libc::SOL_SOCKET, libc::SCM_RIGHTS, data))
+    }
+
+    /// Adds an `SCM_CREDENTIALS` control message carrying `cred`.
+    ///
+    /// The kernel will reject credentials which do not match the sending
+    /// process unless it is privileged.
+    ///
+    /// Only supported on Linux.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn add_credentials(&mut self, cred: &UCred) -> &mut AncillaryData {
+        let cred = libc::ucred {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        };
+        let data = unsafe {
+            std::slice::from_raw_parts(&cred as *const _ as *const u8,
+                                       mem::size_of::<libc::ucred>())
+        };
+        self.push(libc::SOL_SOCKET, libc::SCM_CREDENTIALS, data)
+    }
+
+    /// Returns `true` if no control messages have been added.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all control messages.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.len = 0;
+    }
+
+    fn push(&mut self, level: libc::c_int, ty: libc::c_int, data: &[u8]) -> &mut AncillaryData {
+        unsafe {
+            let space = libc::CMSG_SPACE(data.len() as libc::c_uint) as usize;
+            let offset = self.len;
+            self.len += space;
+            self.buf.resize(self.len.div_ceil(mem::size_of::<usize>()), 0);
+
+            // CMSG_SPACE keeps every header aligned
+            let cmsg = (self.buf.as_mut_ptr() as *mut u8).add(offset) as *mut libc::cmsghdr;
+            (*cmsg).cmsg_level = level;
+            (*cmsg).cmsg_type = ty;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(data.len() as libc::c_uint) as _;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), libc::CMSG_DATA(cmsg), data.len());
+        }
+        self
+    }
+}
+
+/// A buffer receiving the control messages which arrive along with data on a
+/// socket.
+///
+/// File descriptors received in `CmsgEntry::Rights` entries are owned by the
+/// caller, and will leak if they are not closed.
+#[derive(Debug, Clone)]
+pub struct AncillaryMessage {
+    buf: Vec<usize>,
+    capacity: usize,
+    len: usize,
+    truncated: bool,
+}
+
+impl AncillaryMessage {
+    /// Creates a buffer which can hold `capacity` bytes of control messages.
+    pub fn with_capacity(capacity: usize) -> AncillaryMessage {
+        AncillaryMessage {
+            buf: cmsg_buffer(capacity),
+            capacity: capacity,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Returns an iterator over the received control messages.
+    pub fn entries<'a>(&'a self) -> AncillaryEntries<'a> {
+        unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            if self.len > 0 {
+                msg.msg_control = self.buf.as_ptr() as *mut _;
+                msg.msg_controllen = self.len as _;
+            }
+            AncillaryEntries { inner: CmsgIter::from_msg(msg) }
+        }
+    }
+
+    /// Returns `true` if control messages were discarded because they did
+    /// not fit in the buffer.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Discards any received control messages.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+/// A control message received on a socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CmsgEntry {
+    /// File descriptors transferred with `SCM_RIGHTS`.
+    Rights(Vec<RawFd>),
+    /// Process credentials sent with `SCM_CREDENTIALS`.
+    ///
+    /// Only supported on Linux.
+    Credentials(UCred),
+    /// The time at which the kernel received the data, reported with
+    /// `SCM_TIMESTAMP` or `SCM_TIMESTAMPNS`.
+    Timestamp(std::time::SystemTime),
+    /// A control message of some other type.
+    Unknown {
+        /// The originating protocol of the message.
+        level: i32,
+        /// The protocol-specific type of the message.
+        ty: i32,
+        /// The contents of the message.
+        data: Vec<u8>,
+    },
+}
+
+/// An iterator over the control messages in an `AncillaryMessage`.
+pub struct AncillaryEntries<'a> {
+    inner: CmsgIter<'a>,
+}
+
+impl<'a> fmt::Debug for AncillaryEntries<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "AncillaryEntries").finish()
+    }
+}
+
+impl<'a> Iterator for AncillaryEntries<'a> {
+    type Item = CmsgEntry;
+
+    fn next(&mut self) -> Option<CmsgEntry> {
+        self.inner.next().map(|(level, ty, data)| unsafe { parse_cmsg(level, ty, data) })
+    }
+}
+
+/// An iterator over the raw control messages described by a `msghdr`.
+///
+/// Each control message is yielded as its level, type and data. This walks
+/// the headers with `CMSG_FIRSTHDR` and `CMSG_NXTHDR`, stopping at the first
+/// malformed header, and never yields data past the end of the control
+/// buffer.
+pub struct CmsgIter<'a> {
+    msg: libc::msghdr,
+    cur: *mut libc::cmsghdr,
+    _marker: std::marker::PhantomData<&'a [u8]>,
+}
+
+impl<'a> CmsgIter<'a> {
+    /// Creates an iterator over the control messages in the buffer described
+    /// by the `msg_control` and `msg_controllen` fields of `msg`.
+    ///
+    /// # Safety
+    ///
+    /// `msg_control` must point to a buffer of at least `msg_controllen`
+    /// bytes, suitably aligned for a `cmsghdr`, which is valid for reads for
+    /// the lifetime `'a`. This is the case for a buffer filled in by
+    /// `recvmsg` which outlives the iterator.
+    pub unsafe fn new(msg: &'a libc::msghdr) -> CmsgIter<'a> {
+        CmsgIter::from_msg(*msg)
+    }
+
+    unsafe fn from_msg(msg: libc::msghdr) -> CmsgIter<'a> {
+        let cur = if msg.msg_control.is_null() {
+            ptr::null_mut()
+        } else {
+            libc::CMSG_FIRSTHDR(&msg)
+        };
+        CmsgIter {
+            msg: msg,
+            cur: cur,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for CmsgIter<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "CmsgIter").finish()
+    }
+}
+
+impl<'a> Iterator for CmsgIter<'a> {
+    type Item = (i32, i32, &'a [u8]);
+
+    // `cmsg_len` and `msg_controllen` are `usize` on some platforms only
+    #[allow(clippy::unnecessary_cast)]
+    fn next(&mut self) -> Option<(i32, i32, &'a [u8])> {
+        if self.cur.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let cmsg = self.cur;
+            let cmsg_len = (*cmsg).cmsg_len as usize;
+            if cmsg_len < libc::CMSG_LEN(0) as usize {
+                self.cur = ptr::null_mut();
+                return None;
+            }
+            self.cur = libc::CMSG_NXTHDR(&self.msg, cmsg);
+
+            let data = libc::CMSG_DATA(cmsg);
+            let end = (self.msg.msg_control as *const u8).add(self.msg.msg_controllen as usize);
+            let available = (end as usize).saturating_sub(data as usize);
+            let data_len = cmp::min(cmsg_len - libc::CMSG_LEN(0) as usize, available);
+            let data = std::slice::from_raw_parts(data, data_len);
+
+            Some(((*cmsg).cmsg_level, (*cmsg).cmsg_type, data))
+        }
+    }
+}
+
+unsafe fn parse_cmsg(level: libc::c_int, ty: libc::c_int, data: &[u8]) -> CmsgEntry {
+    if level == libc::SOL_SOCKET && ty == libc::SCM_RIGHTS {
+        let mut fds = vec![];
+        for chunk in data.chunks(mem::size_of::<RawFd>()) {
+            if chunk.len() == mem::size_of::<RawFd>() {
+                fds.push(std::ptr::read_unaligned(chunk.as_ptr() as *const RawFd));
+            }
+        }
+        return CmsgEntry::Rights(fds);
+    }
+
+    if level == libc::SOL_SOCKET && ty == libc::SCM_TIMESTAMP &&
+       data.len() >= mem::size_of::<libc::timeval>() {
+        let tv = std::ptr::read_unaligned(data.as_ptr() as *const libc::timeval);
+        return CmsgEntry::Timestamp(system_time(tv.tv_sec as i64, tv.tv_usec as u32 * 1000));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        if level == libc::SOL_SOCKET && ty == libc::SCM_TIMESTAMPNS &&
+           data.len() >= mem::size_of::<libc::timespec>() {
+            let ts = std::ptr::read_unaligned(data.as_ptr() as *const libc::timespec);
+            return CmsgEntry::Timestamp(system_time(ts.tv_sec as i64, ts.tv_nsec as u32));
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        if level == libc::SOL_SOCKET && ty == libc::SCM_CREDENTIALS &&
+           data.len() >= mem::size_of::<libc::ucred>() {
+            let cred = std::ptr::read_unaligned(data.as_ptr() as *const libc::ucred);
+            return CmsgEntry::Credentials(UCred {
+                uid: cred.uid,
+                gid: cred.gid,
+                pid: cred.pid,
+            });
+        }
+    }
+
+    CmsgEntry::Unknown {
+        level: level,
+        ty: ty,
+        data: data.to_vec(),
+    }
+}
+
+fn system_time(secs: i64, nanos: u32) -> std::time::SystemTime {
+    if secs >= 0 {
+        std::time::UNIX_EPOCH + std::time::Duration::new(secs as u64, nanos)
+    } else {
+        std::time::UNIX_EPOCH - std::time::Duration::from_secs(secs.unsigned_abs()) +
+        std::time::Duration::new(0, nanos)
+    }
+}
+
+/// A Unix stream socket.
+///
+/// Writing to a stream whose peer has closed returns a `BrokenPipe` error.
+/// No `SIGPIPE` is raised, so the signal's disposition need not be changed:
+/// `MSG_NOSIGNAL` is passed to every send where the platform supports it,
+/// and on macOS and iOS the `SO_NOSIGPIPE` option is set on every socket the
+/// crate creates. Sockets created elsewhere and converted with `from_raw_fd`
+/// keep their own setting, which `set_nosigpipe` can change on macOS and iOS.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::UnixStream;
+/// use std::io::prelude::*;
+///
+/// let mut stream = UnixStream::connect("/path/to/my/socket").unwrap();
+/// stream.write_all(b"hello world").unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// println!("{}", response);
+/// ```
+pub struct UnixStream {
+    inner: Inner,
+}
+
+fn display_addrs(fmt: &mut fmt::Formatter,
+                 name: &str,
+                 addrs: &[(&str, io::Result<SocketAddr>)])
+                 -> fmt::Result {
+    try!(fmt.write_str(name));
+    let mut first = true;
+    for &(label, ref addr) in addrs {
+        if let Ok(ref addr) = *addr {
+            try!(write!(fmt, "{} {}: {}", if first { " {" } else { "," }, label, addr));
+            first = false;
+        }
+    }
+    if first {
+        Ok(())
+    } else {
+        fmt.write_str(" }")
+    }
+}
+
+impl fmt::Debug for UnixStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = DebugStruct::new(fmt, "UnixStream")
+            .field("fd", &self.inner.0);
+        if let Ok(addr) = self.local_addr() {
+            builder = builder.field("local", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            builder = builder.field("peer", &addr);
+        }
+        builder.finish()
+    }
+}
+
+/// Formats the stream's addresses, such as
+/// `UnixStream { local: /path/to/sock, peer: (unnamed) }`.
+///
+/// Addresses which cannot be retrieved are omitted.
+impl fmt::Display for UnixStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        display_addrs(fmt,
+                      "UnixStream",
+                      &[("local", self.local_addr()), ("peer", self.peer_addr())])
+    }
+}
+
+impl UnixStream {
+    /// Connect to the socket named by `path`.
+    ///
+    /// Linux provides, as a nonportable extension, a separate "abstract"
+    /// address namespace as opposed to filesystem-based addressing. If `path`
+    /// begins with a null byte, it will be interpreted as an "abstract"
+    /// address. Otherwise, it will be interpreted as a "pathname" address,
+    /// corresponding to a path on the filesystem.
+    ///
+    /// This is equivalent to `ConnectOptions::new().connect(path)`.
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        ConnectOptions::new().connect(path)
+    }
+
+    /// Like `connect`, but reports errors as a `UnixSocketError`.
+    ///
+    /// A `PathTooLong` error is returned if `path` is too long to be a socket
+    /// address.
+    pub fn connect_checked<P: AsRef<Path>>(path: P) -> Result<UnixStream, UnixSocketError> {
+        try!(check_path(path.as_ref()));
+        Ok(try!(UnixStream::connect(path)))
+    }
+
+    /// Connect to the socket named by `path`, with the close-on-exec flag
+    /// set on the new socket.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`, so the
+    /// socket cannot leak into a child process spawned concurrently by
+    /// another thread.
+    pub fn connect_cloexec<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        ConnectOptions::new().cloexec(true).connect(path)
+    }
+
+    /// Connect to the socket named by the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn connect_abstract(path: &AbstractPath) -> io::Result<UnixStream> {
+        ConnectOptions::new().connect_abstract(path)
+    }
+
+    /// Connect to the socket at `addr`, such as one returned by `peer_addr`
+    /// or `local_addr`.
+    pub fn connect_addr(addr: &SocketAddr) -> io::Result<UnixStream> {
+        ConnectOptions::new().connect_addr(addr)
+    }
+
+    /// Connect to the socket named by `path`, failing with a `TimedOut` error
+    /// if the connection cannot be established within `timeout`.
+    ///
+    /// Linux does not allow nonblocking connections to a listener whose
+    /// backlog is full to wait for space, so on that platform the send
+    /// timeout of the socket is used to bound a blocking connect instead.
+    pub fn connect_timeout<P: AsRef<Path>>(path: P, timeout: std::time::Duration)
+                                           -> io::Result<UnixStream> {
+        ConnectOptions::new().timeout(timeout).connect(path)
+    }
+
+    /// Starts connecting to the socket named by `path` without blocking.
+    ///
+    /// The returned `PendingConnect` can be polled until the connection has
+    /// been established, and the resulting stream will be in nonblocking
+    /// mode.
+    pub fn connect_nonblocking<P: AsRef<Path>>(path: P) -> io::Result<PendingConnect> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            PendingConnect::new(addr, len)
+        }
+    }
+
+    /// Create an unnamed pair of connected sockets.
+    ///
+    /// Returns two `UnixStream`s which are connected to each other.
+    pub fn unnamed() -> io::Result<(UnixStream, UnixStream)> {
+        let (i1, i2) = try!(Inner::new_pair(libc::SOCK_STREAM));
+        Ok((UnixStream { inner: i1 }, UnixStream { inner: i2 }))
+    }
+
+    /// Create a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixStream` is a reference to the same stream that this
+    /// object references. Both handles will read and write the same stream of
+    /// data, and options set on one stream will be propogated to the other
+    /// stream.
+    pub fn try_clone(&self) -> io::Result<UnixStream> {
+        Ok(UnixStream {
+            inner: try!(self.inner.try_clone())
+        })
+    }
+
+    /// Creates a new independently owned handle to the underlying socket,
+    /// with the close-on-exec flag set on the new handle.
+    ///
+    /// Unlike `try_clone`, the flag is set atomically where the platform
+    /// supports `F_DUPFD_CLOEXEC`, so the new descriptor cannot leak into a
+    /// child process spawned concurrently by another thread.
+    pub fn try_clone_cloexec(&self) -> io::Result<UnixStream> {
+        Ok(UnixStream {
+            inner: try!(self.inner.try_clone_cloexec())
+        })
+    }
+
+    /// Converts a standard library `UnixStream` into this crate's type.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn from_std(stream: std::os::unix::net::UnixStream) -> UnixStream {
+        UnixStream::from(stream)
+    }
+
+    /// Like `from_std`, but first checks that the descriptor refers to a
+    /// Unix stream socket.
+    ///
+    /// The standard library type may have been created with `from_raw_fd`
+    /// from an arbitrary descriptor. An `InvalidInput` error is returned if
+    /// `SO_TYPE` is not `SOCK_STREAM` or the socket is not in the `AF_UNIX`
+    /// family, in which case `stream` is closed.
+    pub fn try_from_std(stream: std::os::unix::net::UnixStream) -> io::Result<UnixStream> {
+        let stream = UnixStream::from_std(stream);
+        let ty: libc::c_int = try!(stream.inner.getsockopt(libc::SOL_SOCKET, libc::SO_TYPE));
+        if ty != libc::SOCK_STREAM {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "file descriptor is not a stream socket"));
+        }
+        // fails unless the socket is in the AF_UNIX family
+        try!(stream.local_addr());
+        Ok(stream)
+    }
+
+    /// Converts the stream into a standard library `UnixStream`.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn into_std(self) -> std::os::unix::net::UnixStream {
+        self.into()
+    }
+
+    /// Splits the stream into a read half and a write half borrowing from
+    /// it.
+    ///
+    /// The halves can be used from separate threads, for example with a
+    /// scoped thread API.
+    pub fn split<'a>(&'a mut self) -> (ReadHalf<'a>, WriteHalf<'a>) {
+        (ReadHalf(self), WriteHalf(self))
+    }
+
+    /// Splits the stream into an owned read half and an owned write half.
+    ///
+    /// The halves share the underlying socket, which is closed once both
+    /// have been dropped. They can be recombined with
+    /// `OwnedReadHalf::reunite`.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let inner = Arc::new(self.inner);
+        (OwnedReadHalf { inner: inner.clone() }, OwnedWriteHalf { inner: inner })
+    }
+
+    /// Returns an iterator over the lines of the stream.
+    ///
+    /// The stream is wrapped in a `BufUnixStream`. Each line is returned
+    /// without its trailing `\n` or `\r\n`, and the iterator ends at EOF.
+    /// To read a single line, keeping its line ending, wrap the stream in a
+    /// `BufUnixStream` and use `read_line`.
+    pub fn lines(self) -> io::Lines<BufUnixStream> {
+        io::BufRead::lines(BufUnixStream::new(self))
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+    }
+
+    /// Returns the socket address of the remote half of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getpeername(self.inner.0, addr, len) })
+    }
+
+    /// Sets the read timeout for the socket.
+    ///
+    /// If the provided value is `None`, then `read` calls will block
+    /// indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.inner.set_timeout(timeout, libc::SO_RCVTIMEO)
+    }
+
+    /// Sets the write timeout for the socket.
+    ///
+    /// If the provided value is `None`, then `write` calls will block
+    /// indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.inner.set_timeout(timeout, libc::SO_SNDTIMEO)
+    }
+
+    /// Returns the read timeout of this socket.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn read_timeout(&self) -> io::Result<Option<std::time::Duration>> {
+        self.inner.timeout(libc::SO_RCVTIMEO)
+    }
+
+    /// Returns the write timeout of this socket.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn write_timeout(&self) -> io::Result<Option<std::time::Duration>> {
+        self.inner.timeout(libc::SO_SNDTIMEO)
+    }
+
+    /// Sets the read timeout of the socket so that `read` calls time out at
+    /// `deadline`.
+    ///
+    /// The timeout is computed from the time remaining when this is called,
+    /// and as with any socket timeout it applies to each `read` call
+    /// separately. If `deadline` has already passed, the smallest possible
+    /// timeout is used. `None` disables the timeout.
+    pub fn set_read_deadline(&self, deadline: Option<std::time::Instant>) -> io::Result<()> {
+        self.inner.set_timeout(deadline.map(timeout_until), libc::SO_RCVTIMEO)
+    }
+
+    /// Sets the write timeout of the socket so that `write` calls time out at
+    /// `deadline`.
+    ///
+    /// This behaves like `set_read_deadline`.
+    pub fn set_write_deadline(&self, deadline: Option<std::time::Instant>) -> io::Result<()> {
+        self.inner.set_timeout(deadline.map(timeout_until), libc::SO_SNDTIMEO)
+    }
+
+    /// Returns the instant at which a `read` call started now would time
+    /// out, based on the socket's read timeout.
+    pub fn read_deadline(&self) -> io::Result<Option<std::time::Instant>> {
+        let timeout = try!(self.inner.timeout(libc::SO_RCVTIMEO));
+        Ok(timeout.map(|timeout| std::time::Instant::now() + timeout))
+    }
+
+    /// Returns the instant at which a `write` call started now would time
+    /// out, based on the socket's write timeout.
+    pub fn write_deadline(&self) -> io::Result<Option<std::time::Instant>> {
+        let timeout = try!(self.inner.timeout(libc::SO_SNDTIMEO));
+        Ok(timeout.map(|timeout| std::time::Instant::now() + timeout))
+    }
+
+    /// Receives data from the socket without removing it from the queue.
+    ///
+    /// The bytes read will be returned again by the next call to `read` or
+    /// `peek`. On success, returns the number of bytes read.
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, libc::MSG_PEEK)
+    }
+
+    /// Receives data from the socket without blocking.
+    ///
+    /// This behaves as though the socket were in nonblocking mode for this
+    /// call only: if no data is available, an error of kind `WouldBlock` is
+    /// returned. The mode set by `set_nonblocking` is left untouched.
+    pub fn recv_nonblocking(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, libc::MSG_DONTWAIT)
+    }
+
+    /// Sends data on the socket without blocking.
+    ///
+    /// If the send buffer is full, an error of kind `WouldBlock` is returned.
+    /// The mode set by `set_nonblocking` is left untouched.
+    pub fn send_nonblocking(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, libc::MSG_DONTWAIT)
+    }
+
+    /// Reads from the socket without blocking, returning a `WouldBlock`
+    /// error if no data is available.
+    ///
+    /// This is the same as `recv_nonblocking`, named after `io::Read`.
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_nonblocking(buf)
+    }
+
+    /// Writes to the socket without blocking, returning a `WouldBlock`
+    /// error if the send buffer is full.
+    ///
+    /// This is the same as `send_nonblocking`, named after `io::Write`.
+    pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.send_nonblocking(buf)
+    }
+
+    /// Reads from the socket referred to by `fd` with `recv(2)`, without
+    /// constructing a `UnixStream`.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be a valid, open socket for the duration of the call. It is
+    /// not closed afterwards.
+    pub unsafe fn read_fd(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+        mem::ManuallyDrop::new(Inner(fd)).recv(buf, 0)
+    }
+
+    /// Writes to the socket referred to by `fd` with `send(2)`, without
+    /// constructing a `UnixStream`.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be a valid, open socket for the duration of the call. It is
+    /// not closed afterwards.
+    pub unsafe fn write_fd(fd: RawFd, buf: &[u8]) -> io::Result<usize> {
+        mem::ManuallyDrop::new(Inner(fd)).send(buf, 0)
+    }
+
+    /// Like `try_read`, except that it reads into a slice of buffers.
+    pub fn try_read_vectored(&self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        let mut ancillary = AncillaryMessage::with_capacity(0);
+        let (count, _) = try!(self.inner.recv_msg_from(bufs, None, &mut ancillary,
+                                                       libc::MSG_DONTWAIT));
+        Ok(count)
+    }
+
+    /// Like `try_write`, except that it writes from a slice of buffers.
+    pub fn try_write_vectored(&self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        self.inner.send_msg_to(bufs, None, &AncillaryData::new(), libc::MSG_DONTWAIT)
+    }
+
+    /// Reads exactly enough data to fill `buf`, using `MSG_WAITALL` so that
+    /// the kernel can usually do so in a single call.
+    ///
+    /// Returns an `UnexpectedEof` error if the peer closes the connection
+    /// before `buf` is filled. Reads interrupted by a signal are resumed. If
+    /// the read timeout expires an error is returned, and the contents of
+    /// `buf` are unspecified.
+    pub fn read_full(&self, buf: &mut [u8]) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.recv(&mut buf[filled..], libc::MSG_WAITALL) {
+                Ok(0) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              "failed to fill whole buffer"))
+                }
+                Ok(count) => filled += count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads exactly enough data to fill `buf` within `timeout`, using
+    /// `MSG_WAITALL` as `read_full` does.
+    ///
+    /// The read timeout of the socket is adjusted while reading and restored
+    /// afterwards, so this should not be used concurrently with other reads
+    /// from the same socket. If `buf` is not filled in time, a `TimedOut`
+    /// error reporting the number of bytes read is returned, and the
+    /// contents of `buf` are unspecified. Returns an `UnexpectedEof` error if
+    /// the peer closes the connection before `buf` is filled.
+    pub fn read_exact_timeout(&self,
+                              buf: &mut [u8],
+                              timeout: std::time::Duration)
+                              -> io::Result<()> {
+        let deadline = std::time::Instant::now() + timeout;
+        let previous = try!(self.inner.timeout(libc::SO_RCVTIMEO));
+        let res = self.read_exact_until(buf, deadline);
+        let restored = self.inner.set_timeout(previous, libc::SO_RCVTIMEO);
+        res.and(restored)
+    }
+
+    fn read_exact_until(&self, buf: &mut [u8], deadline: std::time::Instant) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            if std::time::Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                          format!("timed out after reading {} of {} bytes",
+                                                  filled,
+                                                  buf.len())));
+            }
+
+            try!(self.inner.set_timeout(Some(timeout_until(deadline)), libc::SO_RCVTIMEO));
+            match self.inner.recv(&mut buf[filled..], libc::MSG_WAITALL) {
+                Ok(0) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              "failed to fill whole buffer"))
+                }
+                Ok(count) => filled += count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted ||
+                              e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Receives data from the socket, passing `flags` through to `recv(2)`.
+    ///
+    /// This gives access to options such as `MSG_WAITALL` which have no
+    /// dedicated method. On success, returns the number of bytes read.
+    pub fn recv_with_flags(&self, buf: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+        self.inner.recv(buf, flags)
+    }
+
+    /// Sends data on the socket, passing `flags` through to `send(2)`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_with_flags(&self, buf: &[u8], flags: libc::c_int) -> io::Result<usize> {
+        self.inner.send(buf, flags)
+    }
+
+    /// Returns the credentials of the process on the other end of this
+    /// connection.
+    ///
+    /// The credentials are those of the peer at the time the connection was
+    /// established. On Linux they are retrieved via `SO_PEERCRED`, on macOS,
+    /// iOS and FreeBSD via `LOCAL_PEERCRED`, and on DragonFly BSD, NetBSD and
+    /// OpenBSD via `getpeereid`. Only Linux reports the process ID. Other
+    /// platforms return an `Unsupported` error.
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        self.inner.peer_cred()
+    }
+
+    /// Checks that the peer's user ID, as reported by `peer_cred`, is
+    /// `expected_uid`.
+    ///
+    /// A `PermissionDenied` error is returned if it is not.
+    pub fn check_peer_uid(&self, expected_uid: u32) -> io::Result<()> {
+        self.check_peer_uid_in(&[expected_uid])
+    }
+
+    /// Checks that the peer's user ID, as reported by `peer_cred`, is one of
+    /// `allowed`.
+    ///
+    /// A `PermissionDenied` error is returned if it is not.
+    pub fn check_peer_uid_in(&self, allowed: &[u32]) -> io::Result<()> {
+        let cred = try!(self.peer_cred());
+        check_id("uid", cred.uid, allowed)
+    }
+
+    /// Checks that the peer's group ID, as reported by `peer_cred`, is
+    /// `expected_gid`.
+    ///
+    /// A `PermissionDenied` error is returned if it is not.
+    pub fn check_peer_gid(&self, expected_gid: u32) -> io::Result<()> {
+        self.check_peer_gid_in(&[expected_gid])
+    }
+
+    /// Checks that the peer's group ID, as reported by `peer_cred`, is one of
+    /// `allowed`.
+    ///
+    /// Only the peer's primary group is considered. A `PermissionDenied` error
+    /// is returned if it is not allowed.
+    pub fn check_peer_gid_in(&self, allowed: &[u32]) -> io::Result<()> {
+        let cred = try!(self.peer_cred());
+        check_id("gid", cred.gid, allowed)
+    }
+
+    /// Checks the peer's credentials, as reported by `peer_cred`, against
+    /// `policy`.
+    ///
+    /// The credentials are returned if `policy` returns `true`, and an
+    /// `AuthenticationFailed` error containing them otherwise.
+    pub fn check_peer<F>(&self, policy: F) -> Result<UCred, UnixSocketError>
+        where F: FnOnce(&UCred) -> bool
+    {
+        let cred = try!(self.peer_cred());
+        if policy(&cred) {
+            Ok(cred)
+        } else {
+            Err(UnixSocketError::AuthenticationFailed(cred))
+        }
+    }
+
+    /// Sends data on the socket along with a set of file descriptors.
+    ///
+    /// The file descriptors are sent as an `SCM_RIGHTS` control message, and
+    /// the receiving process will get new descriptors referring to the same
+    /// open files. Ownership of `fds` is not transferred; they remain open in
+    /// this process.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_fds(&self, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        let mut ancillary = AncillaryData::new();
+        try!(ancillary.add_fds(fds));
+        self.send_with_ancillary(buf, &ancillary)
+    }
+
+    /// Receives data from the socket along with up to `max_fds` file
+    /// descriptors.
+    ///
+    /// The caller takes ownership of the returned file descriptors and is
+    /// responsible for closing them. Any descriptors received beyond
+    /// `max_fds` are closed.
+    ///
+    /// On success, returns the number of bytes read and the received file
+    /// descriptors.
+    pub fn recv_fds(&self, buf: &mut [u8], max_fds: usize) -> io::Result<(usize, Vec<RawFd>)> {
+        let space = try!(rights_space(max_fds));
+        let mut ancillary = AncillaryMessage::with_capacity(if max_fds == 0 { 0 } else { space });
+        let count = try!(self.recv_with_ancillary(buf, &mut ancillary));
+
+        let mut fds = vec![];
+        for entry in ancillary.entries() {
+            if let CmsgEntry::Rights(received) = entry {
+                for fd in received {
+                    if fds.len() < max_fds {
+                        fds.push(fd);
+                    } else {
+                        unsafe {
+                            libc::close(fd);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok((count, fds))
+    }
+
+    /// Sends data on the socket along with the control messages in
+    /// `ancillary`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_with_ancillary(&self, buf: &[u8], ancillary: &AncillaryData) -> io::Result<usize> {
+        self.inner.send_msg(&[io::IoSlice::new(buf)], ancillary)
+    }
+
+    /// Sends data gathered from `bufs` along with the control messages in
+    /// `ancillary`, passing `flags` through to `sendmsg(2)`.
+    ///
+    /// This writes a header and body held in separate buffers, together with
+    /// file descriptors or credentials, in a single system call.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn sendmsg_vectored(&self,
+                            bufs: &[io::IoSlice],
+                            ancillary: &AncillaryData,
+                            flags: libc::c_int)
+                            -> io::Result<usize> {
+        self.inner.send_msg_to(bufs, None, ancillary, flags)
+    }
+
+    /// Receives data scattered into `bufs`, storing any control messages that
+    /// arrive with it in `ancillary` and passing `flags` through to
+    /// `recvmsg(2)`.
+    ///
+    /// Control messages which do not fit in the capacity of `ancillary` are
+    /// discarded, which `AncillaryMessage::is_truncated` will report.
+    ///
+    /// On success, returns the number of bytes read.
+    pub fn recvmsg_vectored(&self,
+                            bufs: &mut [io::IoSliceMut],
+                            ancillary: &mut AncillaryMessage,
+                            flags: libc::c_int)
+                            -> io::Result<usize> {
+        self.inner.recv_msg_from(bufs, None, ancillary, flags).map(|(count, _)| count)
+    }
+
+    /// Sends data with the control messages and flags in `msg`, in a single
+    /// call to `sendmsg(2)`.
+    ///
+    /// Streams are always connected, so `msg.addr` should be `None`; most
+    /// platforms reject an address with `EISCONN`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_meta(&self, msg: &SendMsg) -> io::Result<usize> {
+        msg.send(&self.inner)
+    }
+
+    /// Receives data from the socket, storing any control messages that
+    /// arrive with it in `ancillary`.
+    ///
+    /// Control messages which do not fit in the capacity of `ancillary` are
+    /// discarded, which `AncillaryMessage::is_truncated` will report.
+    ///
+    /// On success, returns the number of bytes read.
+    pub fn recv_with_ancillary(&self,
+                               buf: &mut [u8],
+                               ancillary: &mut AncillaryMessage)
+                               -> io::Result<usize> {
+        self.inner.recv_msg(&mut [io::IoSliceMut::new(buf)], ancillary)
+    }
+
+    /// Enables or disables the `SO_NOSIGPIPE` option.
+    ///
+    /// When enabled, writes to a stream whose peer has closed return a
+    /// `BrokenPipe` error instead of raising `SIGPIPE`. It is enabled on
+    /// every socket created by this crate.
+    ///
+    /// Only supported on macOS and iOS.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn set_nosigpipe(&self, nosigpipe: bool) -> io::Result<()> {
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_NOSIGPIPE, nosigpipe as libc::c_int)
+    }
+
+    /// Sends a single byte of out-of-band data with `MSG_OOB`.
+    ///
+    /// Out-of-band data on Unix stream sockets requires Linux 5.15 or newer.
+    pub fn send_oob(&self, byte: u8) -> io::Result<()> {
+        self.inner.send(&[byte], libc::MSG_OOB).map(|_| ())
+    }
+
+    /// Receives a pending byte of out-of-band data with `MSG_OOB`.
+    ///
+    /// This fails if no out-of-band byte is pending, or if `SO_OOBINLINE` is
+    /// enabled, in which case the byte is read as part of the normal data
+    /// stream.
+    pub fn recv_oob(&self) -> io::Result<u8> {
+        let mut byte = [0];
+        let count = try!(self.inner.recv(&mut byte, libc::MSG_OOB));
+        if count == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no out-of-band data"));
+        }
+        Ok(byte[0])
+    }
+
+    /// Enables or disables the `SO_OOBINLINE` option.
+    ///
+    /// When enabled, out-of-band data is placed in the normal data stream
+    /// rather than being read with `recv_oob`.
+    pub fn set_oobinline(&self, oobinline: bool) -> io::Result<()> {
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_OOBINLINE, oobinline as libc::c_int)
+    }
+
+    /// Returns the value of the `SO_OOBINLINE` option.
+    pub fn oobinline(&self) -> io::Result<bool> {
+        let oobinline: libc::c_int = try!(self.inner.getsockopt(libc::SOL_SOCKET,
+                                                                libc::SO_OOBINLINE));
+        Ok(oobinline != 0)
+    }
+
+    /// Sets the value of the `SO_LINGER` option.
+    ///
+    /// `None` disables lingering. The timeout has a granularity of one
+    /// second.
+    ///
+    /// Linux stores the option for Unix sockets but otherwise ignores it:
+    /// written data is queued directly on the peer, so dropping the stream
+    /// neither blocks nor discards that data, whatever the timeout.
+    pub fn set_linger(&self, linger: Option<std::time::Duration>) -> io::Result<()> {
+        let linger = libc::linger {
+            l_onoff: linger.is_some() as libc::c_int,
+            l_linger: linger.map_or(0, |dur| {
+                cmp::min(dur.as_secs(), libc::c_int::max_value() as u64) as libc::c_int
+            }),
+        };
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_LINGER, linger)
+    }
+
+    /// Returns the value of the `SO_LINGER` option.
+    pub fn linger(&self) -> io::Result<Option<std::time::Duration>> {
+        let linger: libc::linger = try!(self.inner.getsockopt(libc::SOL_SOCKET, libc::SO_LINGER));
+        if linger.l_onoff == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(std::time::Duration::from_secs(linger.l_linger as u64)))
+        }
+    }
+
+    /// Returns the number of bytes which can be read without blocking,
+    /// using the `FIONREAD` ioctl.
+    pub fn available_bytes(&self) -> io::Result<usize> {
+        self.inner.available_bytes()
+    }
+
+    /// Sets the size of the socket's send buffer via `SO_SNDBUF`.
+    ///
+    /// The kernel may adjust the requested size; Linux doubles it to leave
+    /// room for bookkeeping overhead and clamps it to `net.core.wmem_max`, so
+    /// `send_buffer_size` will generally not return the value set here.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.inner.set_buffer_size(libc::SO_SNDBUF, size)
+    }
+
+    /// Returns the size of the socket's send buffer.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        self.inner.buffer_size(libc::SO_SNDBUF)
+    }
+
+    /// Sets the size of the socket's receive buffer via `SO_RCVBUF`.
+    ///
+    /// As with `set_send_buffer_size`, the kernel may adjust the requested
+    /// size.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.inner.set_buffer_size(libc::SO_RCVBUF, size)
+    }
+
+    /// Returns the size of the socket's receive buffer.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        self.inner.buffer_size(libc::SO_RCVBUF)
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// This will retrieve the pending error on the socket, if any, clearing
+    /// it in the process.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// While in nonblocking mode, `read` and `write` calls which would otherwise block
+    /// will instead return an error of kind `WouldBlock`.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Returns whether the socket is in nonblocking mode.
+    pub fn nonblocking(&self) -> io::Result<bool> {
+        self.inner.nonblocking()
+    }
+
+    /// Shut down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O calls on the
+    /// specified portions to immediately return with an appropriate value
+    /// (see the documentation of `Shutdown`).
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Shut down the read half of this connection.
+    ///
+    /// This is equivalent to `shutdown(Shutdown::Read)`.
+    pub fn shutdown_read(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Read)
+    }
+
+    /// Shut down the write half of this connection.
+    ///
+    /// This is equivalent to `shutdown(Shutdown::Write)`.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Write)
+    }
+
+    /// Returns whether a read from the stream would return without blocking.
+    ///
+    /// This polls the socket for `POLLIN` with a zero timeout. A stream at
+    /// end of file or with a pending error is also considered readable.
+    pub fn is_readable(&self) -> io::Result<bool> {
+        self.inner.is_readable()
+    }
+
+    /// Returns whether a write to the stream would return without blocking.
+    ///
+    /// This polls the socket for `POLLOUT` with a zero timeout. A stream
+    /// whose peer has hung up or with a pending error is also considered
+    /// writable.
+    pub fn is_writable(&self) -> io::Result<bool> {
+        self.inner.is_writable()
+    }
+}
+
+/// Options used to connect a `UnixStream`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::ConnectOptions;
+/// use std::time::Duration;
+///
+/// let stream = ConnectOptions::new()
+///                  .timeout(Duration::from_secs(5))
+///                  .cloexec(true)
+///                  .connect("/path/to/the/socket")
+///                  .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    timeout: Option<std::time::Duration>,
+    nonblocking: bool,
+    cloexec: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> ConnectOptions {
+        ConnectOptions::new()
+    }
+}
+
+impl ConnectOptions {
+    /// Creates a new set of options with no timeout and all flags disabled.
+    pub fn new() -> ConnectOptions {
+        ConnectOptions {
+            timeout: None,
+            nonblocking: false,
+            cloexec: false,
+        }
+    }
+
+    /// Sets a timeout for establishing the connection.
+    ///
+    /// If the connection cannot be established in time, connecting fails
+    /// with a `TimedOut` error. Linux does not allow nonblocking connections
+    /// to a listener whose backlog is full to wait for space, so on that
+    /// platform the send timeout of the socket is used to bound a blocking
+    /// connect instead.
+    pub fn timeout(mut self, timeout: std::time::Duration) -> ConnectOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// If set, the stream will be left in nonblocking mode once connected.
+    ///
+    /// Without a timeout, the connection itself is also made without
+    /// blocking. If it cannot complete immediately a `WouldBlock` error is
+    /// returned, or the stream is returned while the connection is still in
+    /// progress on platforms which report `EINPROGRESS`.
+    pub fn nonblocking(mut self, nonblocking: bool) -> ConnectOptions {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// If set, the close-on-exec flag will be set on the stream.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`.
+    pub fn cloexec(mut self, cloexec: bool) -> ConnectOptions {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Connects to the socket named by `path`.
+    ///
+    /// As with `UnixStream::connect`, a `path` beginning with a null byte is
+    /// interpreted as an abstract address.
+    ///
+    /// The messages of `NotFound` and `ConnectionRefused` errors include the
+    /// address, which helps to track down misconfigured socket paths.
+    pub fn connect<P: AsRef<Path>>(self, path: P) -> io::Result<UnixStream> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.connect_raw(&addr, len)
+        }
+    }
+
+    /// Connects to the socket named by the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn connect_abstract(self, path: &AbstractPath) -> io::Result<UnixStream> {
+        let (addr, len) = path.sockaddr_un();
+        self.connect_raw(&addr, len)
+    }
+
+    /// Connects to the socket at `addr`.
+    pub fn connect_addr(self, addr: &SocketAddr) -> io::Result<UnixStream> {
+        self.connect_raw(&addr.addr, addr.len)
+    }
+
+    fn connect_raw(self, addr: &libc::sockaddr_un, len: libc::socklen_t) -> io::Result<UnixStream> {
+        self.connect_raw_inner(addr, len).map_err(|err| connect_error(err, addr, len))
+    }
+
+    fn connect_raw_inner(self,
+                         addr: &libc::sockaddr_un,
+                         len: libc::socklen_t)
+                         -> io::Result<UnixStream> {
+        let inner = try!(Inner::with_cloexec(libc::SOCK_STREAM, self.cloexec));
+
+        match self.timeout {
+            Some(timeout) => {
+                try!(connect_with_timeout(&inner, addr, len, timeout));
+                if self.nonblocking {
+                    try!(inner.set_nonblocking(true));
+                }
+            }
+            None if self.nonblocking => {
+                try!(inner.set_nonblocking(true));
+                // a nonblocking connect is never interrupted
+                let res = unsafe {
+                    cvt(libc::connect(inner.0, addr as *const _ as *const _, len))
+                };
+                match res {
+                    Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
+                    res => {
+                        try!(res);
+                    }
+                }
+            }
+            None => unsafe {
+                try!(inner.connect(addr as *const _ as *const _, len));
+            },
+        }
+
+        Ok(UnixStream {
+            inner: inner,
+        })
+    }
+}
+
+// Names the address in the errors most commonly caused by a misconfigured
+// socket path, since the OS's messages don't mention it. The OS error is
+// kept as the source, so its errno can still be recovered.
+fn connect_error(err: io::Error, addr: &libc::sockaddr_un, len: libc::socklen_t) -> io::Error {
+    match err.raw_os_error() {
+        Some(libc::ENOENT) | Some(libc::ECONNREFUSED) => {
+            let addr = SocketAddr {
+                addr: *addr,
+                len: len,
+            };
+            io::Error::new(err.kind(), ConnectError { err: err, addr: addr })
+        }
+        _ => err,
+    }
+}
+
+#[derive(Debug)]
+struct ConnectError {
+    err: io::Error,
+    addr: SocketAddr,
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.err.raw_os_error() == Some(libc::ENOENT) {
+            write!(fmt, "Unix socket path not found: {}", self.addr)
+        } else {
+            write!(fmt, "Unix socket exists but no listener: {}", self.addr)
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+/// A connection started by `UnixStream::connect_nonblocking` which may not
+/// have completed yet.
+///
+/// Linux never reports `EINPROGRESS` for Unix sockets. Instead, connecting to
+/// a listener whose backlog is full fails with `EAGAIN`, so on that platform
+/// the connection is retried each time the `PendingConnect` is polled.
+pub struct PendingConnect {
+    inner: Inner,
+    addr: libc::sockaddr_un,
+    len: libc::socklen_t,
+    state: PendingState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingState {
+    Connected,
+    InProgress,
+    Retry,
+}
+
+/// The result of polling a `PendingConnect`.
+#[derive(Debug)]
+pub enum ConnectState {
+    /// The connection has been established.
+    Connected(UnixStream),
+    /// The connection was not established within the timeout.
+    TimedOut(PendingConnect),
+    /// The connection has not been established yet.
+    InProgress(PendingConnect),
+}
+
+impl fmt::Debug for PendingConnect {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "PendingConnect")
+            .field("fd", &self.inner.0)
+            .field("connected", &(self.state == PendingState::Connected))
+            .finish()
+    }
+}
+
+impl PendingConnect {
+    fn new(addr: libc::sockaddr_un, len: libc::socklen_t) -> io::Result<PendingConnect> {
+        let inner = try!(Inner::new(libc::SOCK_STREAM));
+        try!(inner.set_nonblocking(true));
+        let mut pending = PendingConnect {
+            inner: inner,
+            addr: addr,
+            len: len,
+            state: PendingState::Retry,
+        };
+        try!(pending.try_connect());
+        Ok(pending)
+    }
+
+    fn try_connect(&mut self) -> io::Result<()> {
+        // the socket is nonblocking, so connect is never interrupted
+        let res = unsafe {
+            cvt(libc::connect(self.inner.0, &self.addr as *const _ as *const _, self.len))
+        };
+        self.state = match res {
+            Ok(_) => PendingState::Connected,
+            Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => PendingState::InProgress,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock &&
+                          cfg!(target_os = "linux") => PendingState::Retry,
+            Err(e) => return Err(e),
+        };
+        Ok(())
+    }
+
+    /// Checks whether the connection has been established.
+    ///
+    /// With a timeout of `None` this does not block, returning
+    /// `ConnectState::InProgress` if the connection is not ready. Otherwise
+    /// it waits up to `timeout` for the connection, returning
+    /// `ConnectState::TimedOut` if it is still not ready.
+    pub fn poll(mut self, timeout: Option<std::time::Duration>) -> io::Result<ConnectState> {
+        let ready = match (self.state, timeout) {
+            (PendingState::Connected, _) => true,
+            (PendingState::Retry, None) => {
+                try!(self.try_connect());
+                self.state == PendingState::Connected
+            }
+            (_, Some(timeout)) if timeout.as_secs() == 0 && timeout.subsec_nanos() == 0 => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "cannot set a 0 duration timeout"));
+            }
+            (PendingState::Retry, Some(timeout)) => {
+                try!(self.inner.set_nonblocking(false));
+                let res = unsafe {
+                    connect_with_send_timeout(&self.inner, &self.addr, self.len, timeout)
+                };
+                try!(self.inner.set_nonblocking(true));
+                match res {
+                    Ok(()) => true,
+                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => false,
+                    Err(e) => return Err(e),
+                }
+            }
+            (PendingState::InProgress, None) => {
+                let mut pollfd = libc::pollfd {
+                    fd: self.inner.0,
+                    events: libc::POLLOUT,
+                    revents: 0,
+                };
+                try!(cvt_r(|| unsafe { libc::poll(&mut pollfd, 1, 0) })) > 0
+            }
+            (PendingState::InProgress, Some(timeout)) => {
+                match self.inner.poll(libc::POLLOUT, timeout) {
+                    Ok(()) => true,
+                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => false,
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+
+        if !ready {
+            return Ok(match timeout {
+                Some(_) => ConnectState::TimedOut(self),
+                None => ConnectState::InProgress(self),
+            });
+        }
+        if let Some(err) = try!(self.inner.take_error()) {
+            return Err(err);
+        }
+        Ok(ConnectState::Connected(UnixStream {
+            inner: self.inner,
+        }))
+    }
+
+    /// Blocks until the connection has been established.
+    ///
+    /// If `timeout` is not `None` and the connection is not established in
+    /// time, a `TimedOut` error is returned.
+    pub fn wait(self, timeout: Option<std::time::Duration>) -> io::Result<UnixStream> {
+        // poll requires a finite timeout, so wait in slices
+        let slice = timeout.unwrap_or(std::time::Duration::from_secs(60));
+        let mut pending = self;
+        loop {
+            match try!(pending.poll(Some(slice))) {
+                ConnectState::Connected(stream) => return Ok(stream),
+                ConnectState::TimedOut(_) if timeout.is_some() => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "operation timed out"));
+                }
+                ConnectState::TimedOut(p) | ConnectState::InProgress(p) => pending = p,
+            }
+        }
+    }
+}
+
+impl AsRawFd for PendingConnect {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+fn connect_with_timeout(inner: &Inner,
+                        addr: &libc::sockaddr_un,
+                        len: libc::socklen_t,
+                        timeout: std::time::Duration)
+                        -> io::Result<()> {
+    if timeout.as_secs() == 0 && timeout.subsec_nanos() == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "cannot set a 0 duration timeout"));
+    }
+
+    unsafe {
+        try!(inner.set_nonblocking(true));
+        let ret = libc::connect(inner.0, addr as *const _ as *const _, len);
+        try!(inner.set_nonblocking(false));
+
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINPROGRESS) => {
+                    try!(inner.poll(libc::POLLOUT, timeout));
+                    if let Some(err) = try!(inner.take_error()) {
+                        return Err(err);
+                    }
+                }
+                Some(libc::EAGAIN) if cfg!(target_os = "linux") => {
+                    try!(connect_with_send_timeout(inner, addr, len, timeout));
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+    Ok(())
+}
+
+unsafe fn connect_with_send_timeout(inner: &Inner,
+                                    addr: &libc::sockaddr_un,
+                                    len: libc::socklen_t,
+                                    timeout: std::time::Duration)
+                                    -> io::Result<()> {
+    let secs = cmp::min(timeout.as_secs(), libc::time_t::max_value() as u64);
+    let mut tv = libc::timeval {
+        tv_sec: secs as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+    if tv.tv_sec == 0 && tv.tv_usec == 0 {
+        tv.tv_usec = 1;
+    }
+    try!(inner.setsockopt(libc::SOL_SOCKET, libc::SO_SNDTIMEO, tv));
+
+    let ret = inner.connect(addr as *const _ as *const _, len);
+
+    let tv = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    try!(inner.setsockopt(libc::SOL_SOCKET, libc::SO_SNDTIMEO, tv));
+
+    match ret {
+        Ok(_) => Ok(()),
+        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "operation timed out"))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// cmsghdr must be aligned like a size_t, which a Vec<u8> does not guarantee
+fn cmsg_buffer(space: usize) -> Vec<usize> {
+    vec![0; space.div_ceil(mem::size_of::<usize>())]
+}
+
+fn would_block_to_none<T>(res: io::Result<T>) -> io::Result<Option<T>> {
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn calc_len(buf: &[u8]) -> libc::size_t {
+    cmp::min(libc::size_t::max_value() as usize, buf.len()) as libc::size_t
+}
+
+// The kernel rejects more than IOV_MAX buffers (UIO_MAXIOV on Linux), which
+// is 1024 on all supported platforms.
+fn calc_iovlen(bufs: usize) -> usize {
+    cmp::min(bufs, 1024)
+}
+
+impl io::Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut &*self, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        io::Read::read_vectored(&mut &*self, bufs)
+    }
+}
+
+impl<'a> io::Read for &'a UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, 0)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        self.inner.recv_msg(bufs, &mut AncillaryMessage::with_capacity(0))
+    }
+}
+
+impl io::Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut &*self, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        io::Write::write_vectored(&mut &*self, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut &*self)
+    }
+}
+
+impl<'a> io::Write for &'a UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, 0)
+    }
+
+    // sendmsg rather than writev so that MSG_NOSIGNAL can be passed
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        self.inner.send_msg(bufs, &AncillaryData::new())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+#[cfg(feature = "from_raw_fd")]
+/// Requires the `from_raw_fd` feature.
+impl std::os::unix::io::FromRawFd for UnixStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
+        UnixStream {
+            inner: Inner(fd)
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl std::os::unix::io::AsFd for UnixStream {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.0) }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<std::os::unix::io::OwnedFd> for UnixStream {
+    fn from(fd: std::os::unix::io::OwnedFd) -> UnixStream {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixStream {
+            inner: Inner(fd.into_raw_fd())
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<UnixStream> for std::os::unix::io::OwnedFd {
+    fn from(socket: UnixStream) -> std::os::unix::io::OwnedFd {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::io::OwnedFd::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+impl From<std::os::unix::net::UnixStream> for UnixStream {
+    fn from(socket: std::os::unix::net::UnixStream) -> UnixStream {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixStream {
+            inner: Inner(socket.into_raw_fd())
+        }
+    }
+}
+
+impl From<UnixStream> for std::os::unix::net::UnixStream {
+    fn from(socket: UnixStream) -> std::os::unix::net::UnixStream {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::net::UnixStream::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+/// The read half of a `UnixStream`, created by `UnixStream::split`.
+#[derive(Debug)]
+pub struct ReadHalf<'a>(&'a UnixStream);
+
+impl<'a> io::Read for ReadHalf<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut self.0, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        io::Read::read_vectored(&mut self.0, bufs)
+    }
+}
+
+impl<'a> AsRawFd for ReadHalf<'a> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The write half of a `UnixStream`, created by `UnixStream::split`.
+#[derive(Debug)]
+pub struct WriteHalf<'a>(&'a UnixStream);
+
+impl<'a> io::Write for WriteHalf<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut self.0, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        io::Write::write_vectored(&mut self.0, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.0)
+    }
+}
+
+impl<'a> AsRawFd for WriteHalf<'a> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The owned read half of a `UnixStream`, created by
+/// `UnixStream::into_split`.
+pub struct OwnedReadHalf {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for OwnedReadHalf {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "OwnedReadHalf")
+            .field("fd", &self.inner.0)
+            .finish()
+    }
+}
+
+impl OwnedReadHalf {
+    /// Recombines this half with the write half it was split from.
+    ///
+    /// If `write` came from a different stream, both halves are returned in
+    /// the error.
+    pub fn reunite(self, write: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+        if !Arc::ptr_eq(&self.inner, &write.inner) {
+            return Err(ReuniteError(self, write));
+        }
+
+        drop(write);
+        match Arc::try_unwrap(self.inner) {
+            Ok(inner) => Ok(UnixStream { inner: inner }),
+            Err(_) => unreachable!("both halves were consumed"),
+        }
+    }
+}
+
+impl io::Read for OwnedReadHalf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, 0)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        self.inner.recv_msg(bufs, &mut AncillaryMessage::with_capacity(0))
+    }
+}
+
+impl AsRawFd for OwnedReadHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+/// The owned write half of a `UnixStream`, created by
+/// `UnixStream::into_split`.
+pub struct OwnedWriteHalf {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for OwnedWriteHalf {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "OwnedWriteHalf")
+            .field("fd", &self.inner.0)
+            .finish()
+    }
+}
+
+impl io::Write for OwnedWriteHalf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, 0)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        self.inner.send_msg(bufs, &AncillaryData::new())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for OwnedWriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+/// An error returned by `OwnedReadHalf::reunite` when the halves did not
+/// come from the same stream.
+///
+/// The halves are returned unchanged.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("tried to reunite halves that are not from the same stream")
+    }
+}
+
+impl std::error::Error for ReuniteError {}
+
+/// A `UnixStream` with buffering for both reads and writes.
+///
+/// Buffered writes are flushed before the stream blocks waiting for data to
+/// read, so request/response protocols work without explicit flushes. Any
+/// buffered writes are also flushed when the `BufUnixStream` is dropped, with
+/// errors ignored.
+///
+/// Methods of the underlying stream, such as `peer_addr` or
+/// `set_read_deadline`, are accessible through `Deref`. Reading from or
+/// writing to that stream directly will bypass the buffers.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::{BufUnixStream, UnixStream};
+/// use std::io::prelude::*;
+///
+/// let stream = UnixStream::connect("/path/to/my/socket").unwrap();
+/// let mut stream = BufUnixStream::new(stream);
+/// stream.write_all(b"PING\n").unwrap();
+/// let mut response = String::new();
+/// stream.read_line(&mut response).unwrap();
+/// ```
+pub struct BufUnixStream {
+    // Only None once taken by into_inner
+    inner: Option<UnixStream>,
+    read_buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    write_buf: Vec<u8>,
+}
+
+impl fmt::Debug for BufUnixStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "BufUnixStream")
+            .field("stream", self.get_ref())
+            .field("read_buffer", &format!("{}/{}", self.filled - self.pos, self.read_buf.len()))
+            .field("write_buffer",
+                   &format!("{}/{}", self.write_buf.len(), self.write_buf.capacity()))
+            .finish()
+    }
+}
+
+impl BufUnixStream {
+    /// Creates a new `BufUnixStream` with 8 KiB read and write buffers.
+    pub fn new(stream: UnixStream) -> BufUnixStream {
+        BufUnixStream::with_capacities(stream, 8 * 1024, 8 * 1024)
+    }
+
+    /// Creates a new `BufUnixStream` with the specified buffer capacities.
+    ///
+    /// A read capacity of 0 is raised to 1, since reading into an empty
+    /// buffer would be indistinguishable from end of file.
+    pub fn with_capacities(stream: UnixStream,
+                           read_capacity: usize,
+                           write_capacity: usize)
+                           -> BufUnixStream {
+        BufUnixStream {
+            inner: Some(stream),
+            read_buf: vec![0; cmp::max(read_capacity, 1)],
+            pos: 0,
+            filled: 0,
+            write_buf: Vec::with_capacity(write_capacity),
+        }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &UnixStream {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to the stream directly will bypass the
+    /// buffers.
+    pub fn get_mut(&mut self) -> &mut UnixStream {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Flushes the write buffer and returns the underlying stream.
+    ///
+    /// Any buffered data which has not been read is lost.
+    pub fn into_inner(mut self) -> io::Result<UnixStream> {
+        try!(self.flush_buf());
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let mut ret = Ok(());
+        while written < self.write_buf.len() {
+            match io::Write::write(self.inner.as_mut().unwrap(), &self.write_buf[written..]) {
+                Ok(0) => {
+                    ret = Err(io::Error::new(io::ErrorKind::WriteZero,
+                                             "failed to write the buffered data"));
+                    break;
+                }
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    ret = Err(e);
+                    break;
+                }
+            }
+        }
+        self.write_buf.drain(..written);
+        ret
+    }
+}
+
+impl io::Read for BufUnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Skip the buffer entirely for large reads
+        if self.pos == self.filled && buf.len() >= self.read_buf.len() {
+            try!(self.flush_buf());
+            return io::Read::read(self.get_mut(), buf);
+        }
+
+        let count = {
+            let available = try!(io::BufRead::fill_buf(self));
+            let count = cmp::min(available.len(), buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        io::BufRead::consume(self, count);
+        Ok(count)
+    }
+}
+
+impl io::BufRead for BufUnixStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            try!(self.flush_buf());
+            self.filled = try!(io::Read::read(self.inner.as_mut().unwrap(), &mut self.read_buf));
+            self.pos = 0;
+        }
+        Ok(&self.read_buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.filled);
+    }
+}
+
+impl io::Write for BufUnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.write_buf.len() + buf.len() > self.write_buf.capacity() {
+            try!(self.flush_buf());
+        }
+        if buf.len() >= self.write_buf.capacity() {
+            io::Write::write(self.get_mut(), buf)
+        } else {
+            self.write_buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.flush_buf());
+        io::Write::flush(self.get_mut())
+    }
+}
+
+impl Drop for BufUnixStream {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.flush_buf();
+        }
+    }
+}
+
+impl Deref for BufUnixStream {
+    type Target = UnixStream;
+
+    fn deref(&self) -> &UnixStream {
+        self.get_ref()
+    }
+}
+
+/// A `UnixStream` carrying length-prefixed messages.
+///
+/// Each frame is sent as a 4 byte big-endian length followed by that many
+/// bytes of data.
+#[derive(Debug)]
+pub struct FramedUnixStream {
+    inner: BufUnixStream,
+    max_frame_size: usize,
+}
+
+impl FramedUnixStream {
+    /// Creates a new `FramedUnixStream` which accepts frames of up to 16 MiB.
+    pub fn new(stream: UnixStream) -> FramedUnixStream {
+        FramedUnixStream::with_max_frame_size(stream, 16 * 1024 * 1024)
+    }
+
+    /// Creates a new `FramedUnixStream` which accepts frames of up to
+    /// `max_frame_size` bytes.
+    ///
+    /// Receiving a frame which announces a larger size will fail with an
+    /// `InvalidData` error, so a misbehaving peer cannot cause an unbounded
+    /// allocation.
+    pub fn with_max_frame_size(stream: UnixStream, max_frame_size: usize) -> FramedUnixStream {
+        FramedUnixStream {
+            inner: BufUnixStream::new(stream),
+            max_frame_size: max_frame_size,
+        }
+    }
+
+    /// Returns the largest frame which will be accepted.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &UnixStream {
+        self.inner.get_ref()
+    }
+
+    /// Returns the underlying stream.
+    ///
+    /// Any buffered data which has not been received as a frame is lost.
+    pub fn into_inner(self) -> io::Result<UnixStream> {
+        self.inner.into_inner()
+    }
+
+    /// Sends `data` as a single frame.
+    pub fn send_frame(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() > u32::max_value() as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "frame is too large to be sent"));
+        }
+
+        let len = data.len() as u32;
+        let header = [(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        try!(io::Write::write_all(&mut self.inner, &header));
+        try!(io::Write::write_all(&mut self.inner, data));
+        io::Write::flush(&mut self.inner)
+    }
+
+    /// Receives a single frame.
+    pub fn recv_frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        try!(self.recv_frame_into(&mut buf));
+        Ok(buf)
+    }
+
+    /// Receives a single frame into `buf`, replacing its contents.
+    ///
+    /// This allows the allocation to be reused across frames.
+    pub fn recv_frame_into(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        self.read_frame(buf).map_err(io::Error::from)
+    }
+
+    /// Like `recv_frame`, but reports errors as a `UnixSocketError`.
+    ///
+    /// A `FrameTooLarge` error is returned if the frame exceeds the maximum
+    /// frame size.
+    pub fn recv_frame_checked(&mut self) -> Result<Vec<u8>, UnixSocketError> {
+        let mut buf = vec![];
+        try!(self.read_frame(&mut buf));
+        Ok(buf)
+    }
+
+    fn read_frame(&mut self, buf: &mut Vec<u8>) -> Result<(), UnixSocketError> {
+        let mut header = [0; 4];
+        try!(io::Read::read_exact(&mut self.inner, &mut header));
+        let len = header.iter().fold(0, |len, &b| len << 8 | b as usize);
+        if len > self.max_frame_size {
+            return Err(UnixSocketError::FrameTooLarge {
+                size: len,
+                max: self.max_frame_size,
+            });
+        }
+
+        buf.clear();
+        buf.resize(len, 0);
+        try!(io::Read::read_exact(&mut self.inner, buf));
+        Ok(())
+    }
+}
+
+/// Options used to create a `UnixListener`.
+///
+/// These options are applied to the socket before it is bound and starts
+/// listening.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::ListenerOptions;
+///
+/// let listener = ListenerOptions::new()
+///                    .backlog(512)
+///                    .nonblocking(true)
+///                    .cloexec(true)
+///                    .bind("/path/to/the/socket")
+///                    .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ListenerOptions {
+    backlog: i32,
+    nonblocking: bool,
+    cloexec: bool,
+    reuse_addr: bool,
+}
+
+impl Default for ListenerOptions {
+    fn default() -> ListenerOptions {
+        ListenerOptions::new()
+    }
+}
+
+impl ListenerOptions {
+    /// Creates a new set of options with a backlog of 128 and all flags
+    /// disabled.
+    pub fn new() -> ListenerOptions {
+        ListenerOptions {
+            backlog: 128,
+            nonblocking: false,
+            cloexec: false,
+            reuse_addr: false,
+        }
+    }
+
+    /// Sets the length of the pending connection queue.
+    pub fn backlog(mut self, backlog: i32) -> ListenerOptions {
+        self.backlog = backlog;
+        self
+    }
+
+    /// If set, the listener will be in nonblocking mode.
+    pub fn nonblocking(mut self, nonblocking: bool) -> ListenerOptions {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// If set, the close-on-exec flag will be set on the listener.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`.
+    pub fn cloexec(mut self, cloexec: bool) -> ListenerOptions {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// If set, the `SO_REUSEADDR` option will be enabled before binding.
+    ///
+    /// Most platforms, including Linux, ignore this option for Unix sockets;
+    /// a socket file which already exists must still be removed first.
+    pub fn reuse_addr(mut self, reuse_addr: bool) -> ListenerOptions {
+        self.reuse_addr = reuse_addr;
+        self
+    }
+
+    /// Creates a new `UnixListener` bound to the specified socket.
+    ///
+    /// As with `UnixListener::bind`, a `path` beginning with a null byte is
+    /// interpreted as an abstract address.
+    pub fn bind<P: AsRef<Path>>(self, path: P) -> io::Result<UnixListener> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.bind_raw(&addr, len)
+        }
+    }
+
+    /// Creates a new `UnixListener` bound to the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn bind_abstract(self, path: &AbstractPath) -> io::Result<UnixListener> {
+        let (addr, len) = path.sockaddr_un();
+        self.bind_raw(&addr, len)
+    }
+
+    /// Creates a new `UnixListener` bound to `addr`.
+    pub fn bind_addr(self, addr: &SocketAddr) -> io::Result<UnixListener> {
+        self.bind_raw(&addr.addr, addr.len)
+    }
+
+    fn bind_raw(self, addr: &libc::sockaddr_un, len: libc::socklen_t) -> io::Result<UnixListener> {
+        let inner = try!(Inner::with_cloexec(libc::SOCK_STREAM, self.cloexec));
+        if self.nonblocking {
+            try!(inner.set_nonblocking(true));
+        }
+        if self.reuse_addr {
+            try!(inner.setsockopt(libc::SOL_SOCKET, libc::SO_REUSEADDR, 1 as libc::c_int));
+        }
+
+        unsafe {
+            try!(cvt_r(|| libc::bind(inner.0, addr as *const _ as *const _, len)));
+            try!(cvt_r(|| libc::listen(inner.0, self.backlog)));
+        }
+
+        Ok(UnixListener {
+            inner: inner,
+        })
+    }
+}
+
+/// A structure representing a Unix domain socket server.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::thread;
+/// use unix_socket::{UnixStream, UnixListener};
+///
+/// fn handle_client(stream: UnixStream) {
+///     // ...
+/// }
+///
+/// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+///
+/// // accept connections and process them, spawning a new thread for each one
+/// for stream in listener.incoming() {
+///     match stream {
+///         Ok(stream) => {
+///             /* connection succeeded */
+///             thread::spawn(|| handle_client(stream));
+///         }
+///         Err(err) => {
+///             /* connection failed */
+///             break;
+///         }
+///     }
+/// }
+///
+/// // close the listener socket
+/// drop(listener);
+/// ```
+pub struct UnixListener {
+    inner: Inner,
+}
+
+impl fmt::Debug for UnixListener {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = DebugStruct::new(fmt, "UnixListener")
+            .field("fd", &self.inner.0);
+        if let Ok(addr) = self.local_addr() {
+            builder = builder.field("local", &addr);
+        }
+        builder.finish()
+    }
+}
+
+/// Formats the listener's address, such as `UnixListener { local: @name }`.
+///
+/// The address is omitted if it cannot be retrieved.
+impl fmt::Display for UnixListener {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        display_addrs(fmt, "UnixListener", &[("local", self.local_addr())])
+    }
+}
+
+impl UnixListener {
+    /// Creates a new `UnixListener` which will be bound to the specified
+    /// socket.
+    ///
+    /// Linux provides, as a nonportable extension, a separate "abstract"
+    /// address namespace as opposed to filesystem-based addressing. If `path`
+    /// begins with a null byte, it will be interpreted as an "abstract"
+    /// address. Otherwise, it will be interpreted as a "pathname" address,
+    /// corresponding to a path on the filesystem.
+    ///
+    /// This is equivalent to `ListenerOptions::new().bind(path)`.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        ListenerOptions::new().bind(path)
+    }
+
+    /// Like `bind`, but reports errors as a `UnixSocketError`.
+    ///
+    /// A `PathTooLong` error is returned if `path` is too long to be a socket
+    /// address.
+    pub fn bind_checked<P: AsRef<Path>>(path: P) -> Result<UnixListener, UnixSocketError> {
+        try!(check_path(path.as_ref()));
+        Ok(try!(UnixListener::bind(path)))
+    }
+
+    /// Creates a new `UnixListener` which will be bound to the specified
+    /// socket, with a pending connection queue of length `backlog`.
+    ///
+    /// `bind` uses a backlog of 128.
+    pub fn bind_with_backlog<P: AsRef<Path>>(path: P, backlog: i32) -> io::Result<UnixListener> {
+        ListenerOptions::new().backlog(backlog).bind(path)
+    }
+
+    /// Creates a new `UnixListener` bound to the specified socket, with the
+    /// close-on-exec flag set on the new socket.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`, so the
+    /// socket cannot leak into a child process spawned concurrently by
+    /// another thread.
+    pub fn bind_cloexec<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        ListenerOptions::new().cloexec(true).bind(path)
+    }
+
+    /// Creates a new `UnixListener` bound to the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn bind_abstract(path: &AbstractPath) -> io::Result<UnixListener> {
+        ListenerOptions::new().bind_abstract(path)
+    }
+
+    /// Creates a new `UnixListener` bound to `addr`.
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixListener> {
+        ListenerOptions::new().bind_addr(addr)
+    }
+
+    /// Creates a new `UnixListener` bound to the specified path, atomically
+    /// replacing any socket file which already exists there.
+    ///
+    /// The listener is bound to a temporary path in the same directory which
+    /// is then renamed over `path`, so clients never observe a missing socket
+    /// file. Note that `local_addr` will continue to report the temporary
+    /// path, since that is the name the socket was bound with.
+    ///
+    /// Abstract addresses have no socket file and cannot be replaced this
+    /// way.
+    pub fn bind_replace<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = path.as_ref();
+        if path.as_os_str().as_bytes().first() == Some(&0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "abstract addresses cannot be replaced"));
+        }
+        let dir = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(_)) => dir,
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "path does not name a file"))
+            }
+        };
+
+        let mut attempts = 0;
+        let (listener, tmp_path) = loop {
+            let tmp_path = dir.join(format!(".{}.{}.sock",
+                                            unsafe { libc::getpid() },
+                                            COUNTER.fetch_add(1, atomic::Ordering::Relaxed)));
+            match UnixListener::bind(&tmp_path) {
+                Ok(listener) => break (listener, tmp_path),
+                Err(ref e) if e.kind() == io::ErrorKind::AddrInUse && attempts < 10 => {
+                    attempts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        if let Err(e) = fs::rename(&tmp_path, path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        Ok(listener)
+    }
+
+    /// Accepts a new incoming connection to this listener.
+    ///
+    /// The close-on-exec flag is set on the returned stream. Where supported
+    /// this is done atomically with `accept4`.
+    pub fn accept(&self) -> io::Result<UnixStream> {
+        self.accept_inner(false).map(|inner| UnixStream { inner: inner })
+    }
+
+    /// Accepts a new incoming connection to this listener, returning a
+    /// stream which is already in nonblocking mode.
+    ///
+    /// Where supported both the nonblocking and close-on-exec flags are set
+    /// atomically with `accept4`, avoiding a separate `set_nonblocking` call.
+    pub fn accept_nonblocking(&self) -> io::Result<UnixStream> {
+        self.accept_inner(true).map(|inner| UnixStream { inner: inner })
+    }
+
+    fn accept_inner(&self, nonblocking: bool) -> io::Result<Inner> {
+        unsafe { self.inner.accept(ptr::null_mut(), ptr::null_mut(), nonblocking) }
+    }
+
+    /// Accepts a new incoming connection to this listener, returning the
+    /// address of the connecting socket along with the stream.
+    ///
+    /// Clients which did not bind their socket before connecting will have
+    /// an unnamed address.
+    pub fn accept_addr(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        let mut inner = None;
+        let addr = SocketAddr::new(|addr, len| {
+            inner = Some(unsafe { self.inner.accept(addr, len, false) });
+            0
+        });
+
+        let inner = try!(inner.unwrap());
+        Ok((UnixStream { inner: inner }, try!(addr)))
+    }
+
+    /// Accepts a new incoming connection to this listener if one is pending.
+    ///
+    /// This is intended for listeners in nonblocking mode: rather than
+    /// failing with a `WouldBlock` error when no connection is pending,
+    /// `Ok(None)` is returned. Other errors are propagated.
+    pub fn try_accept(&self) -> io::Result<Option<UnixStream>> {
+        would_block_to_none(self.accept())
+    }
+
+    /// Like `try_accept`, but also returns the address of the connecting
+    /// socket, as `accept_addr` does.
+    pub fn try_accept_addr(&self) -> io::Result<Option<(UnixStream, SocketAddr)>> {
+        would_block_to_none(self.accept_addr())
+    }
+
+    /// Create a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixListener` is a reference to the same socket that this
+    /// object references. Both handles can be used to accept incoming
+    /// connections and options set on one listener will affect the other.
+    pub fn try_clone(&self) -> io::Result<UnixListener> {
+        Ok(UnixListener {
+            inner: try!(self.inner.try_clone())
+        })
+    }
+
+    /// Converts a standard library `UnixListener` into this crate's type.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn from_std(listener: std::os::unix::net::UnixListener) -> UnixListener {
+        UnixListener::from(listener)
+    }
+
+    /// Converts the listener into a standard library `UnixListener`.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn into_std(self) -> std::os::unix::net::UnixListener {
+        self.into()
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+    }
+
+    /// Returns an iterator over incoming connections, along with the
+    /// addresses of the connecting sockets.
+    ///
+    /// The iterator will never return `None`.
+    pub fn incoming_with_addr<'a>(&'a self) -> IncomingWithAddr<'a> {
+        IncomingWithAddr {
+            listener: self
+        }
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// This will retrieve the pending error on the socket, if any, clearing
+    /// it in the process.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// While in nonblocking mode, `accept` calls which would otherwise block
+    /// will instead return an error of kind `WouldBlock`.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Returns whether the socket is in nonblocking mode.
+    pub fn nonblocking(&self) -> io::Result<bool> {
+        self.inner.nonblocking()
+    }
+
+    /// Enables or disables the `SO_REUSEADDR` option.
+    ///
+    /// Unlike with TCP, this option has no effect on binding for Unix
+    /// sockets on most platforms, including Linux: a socket file which
+    /// already exists must still be removed first. `bind_replace` is usually
+    /// the better tool for rebinding a stale path.
+    pub fn set_reuseaddr(&self, reuseaddr: bool) -> io::Result<()> {
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_REUSEADDR, reuseaddr as libc::c_int)
+    }
+
+    /// Returns the value of the `SO_REUSEADDR` option.
+    pub fn reuseaddr(&self) -> io::Result<bool> {
+        let reuseaddr: libc::c_int = try!(self.inner.getsockopt(libc::SOL_SOCKET,
+                                                                libc::SO_REUSEADDR));
+        Ok(reuseaddr != 0)
+    }
+
+    /// Changes the length of the pending connection queue of this listener.
+    ///
+    /// Not all platforms support adjusting the backlog of a listening socket.
+    pub fn set_backlog(&self, backlog: i32) -> io::Result<()> {
+        unsafe {
+            cvt_r(|| libc::listen(self.inner.0, backlog)).map(|_| ())
+        }
+    }
+
+    /// Accepts connections in a loop, calling `handler` on each in a new
+    /// thread.
+    ///
+    /// Accepting is retried after `Interrupted` and `ConnectionAborted`
+    /// errors. Any other error, such as the one returned once the listener
+    /// has been shut down, ends the loop and is returned.
+    pub fn accept_loop<F>(&self, handler: F) -> io::Error
+        where F: Fn(UnixStream) + Send + Sync + 'static
+    {
+        let handler = Arc::new(handler);
+        loop {
+            match self.accept() {
+                Ok(stream) => {
+                    let handler = handler.clone();
+                    std::thread::spawn(move || handler(stream));
+                }
+                Err(ref e) if is_transient_accept_error(e) => {}
+                Err(e) => return e,
+            }
+        }
+    }
+
+    /// Accepts connections on `thread_count` threads, each of which calls
+    /// `handler` on the connections it accepts before accepting the next.
+    ///
+    /// Errors are handled as by `accept_loop`, with each error ending the
+    /// thread which received it. This returns once every thread has ended,
+    /// with the first error received. An `InvalidInput` error is returned
+    /// immediately if `thread_count` is 0.
+    pub fn accept_loop_threads<F>(self, thread_count: usize, handler: F) -> io::Error
+        where F: Fn(UnixStream) + Send + Sync + 'static
+    {
+        if thread_count == 0 {
+            return io::Error::new(io::ErrorKind::InvalidInput, "thread count must be nonzero");
+        }
+
+        let listener = Arc::new(self);
+        let handler = Arc::new(handler);
+        let threads = (0..thread_count).map(|_| {
+            let listener = listener.clone();
+            let handler = handler.clone();
+            std::thread::spawn(move || {
+                loop {
+                    match listener.accept() {
+                        Ok(stream) => handler(stream),
+                        Err(ref e) if is_transient_accept_error(e) => {}
+                        Err(e) => return e,
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        let mut first_err = None;
+        for thread in threads {
+            let err = match thread.join() {
+                Ok(err) => err,
+                // io::Error::other is newer than the compilers this crate supports
+                #[allow(clippy::io_other_error)]
+                Err(_) => io::Error::new(io::ErrorKind::Other, "accept thread panicked"),
+            };
+            first_err = first_err.or(Some(err));
+        }
+        first_err.unwrap()
+    }
+
+    /// Returns an iterator over incoming connections.
+    ///
+    /// The iterator will never return `None`.
+    pub fn incoming<'a>(&'a self) -> Incoming<'a> {
+        Incoming {
+            listener: self
+        }
+    }
+
+    /// Returns an iterator over incoming connections which takes ownership
+    /// of the listener.
+    ///
+    /// Unlike `incoming`, the iterator has no lifetime parameter, so it can
+    /// be moved into another thread.
+    ///
+    /// The iterator will never return `None`.
+    pub fn into_incoming(self) -> OwnedIncoming {
+        OwnedIncoming {
+            listener: self
+        }
+    }
+
+    /// Returns an iterator over incoming connections which ends at the first
+    /// error.
+    ///
+    /// The error which ended the iteration can be retrieved with
+    /// `FallibleIncoming::last_error`.
+    pub fn incoming_fallible<'a>(&'a self) -> FallibleIncoming<'a> {
+        FallibleIncoming {
+            listener: self,
+            last_error: None,
+        }
+    }
+
+    /// Returns an iterator over incoming connections whose peer address is
+    /// accepted by `pred`.
+    ///
+    /// Connections rejected by `pred` are shut down and closed. If the peer
+    /// address cannot be retrieved, `pred` is passed an unnamed address.
+    ///
+    /// The iterator will never return `None`.
+    pub fn incoming_filter<'a, F>(&'a self, pred: F) -> FilteredIncoming<'a, F>
+        where F: Fn(&SocketAddr) -> bool
+    {
+        FilteredIncoming {
+            listener: self,
+            pred: pred,
+        }
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+#[cfg(feature = "from_raw_fd")]
+/// Requires the `from_raw_fd` feature.
+impl std::os::unix::io::FromRawFd for UnixListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
+        UnixListener {
+            inner: Inner(fd)
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl std::os::unix::io::AsFd for UnixListener {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.0) }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<std::os::unix::io::OwnedFd> for UnixListener {
+    fn from(fd: std::os::unix::io::OwnedFd) -> UnixListener {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixListener {
+            inner: Inner(fd.into_raw_fd())
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<UnixListener> for std::os::unix::io::OwnedFd {
+    fn from(socket: UnixListener) -> std::os::unix::io::OwnedFd {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::io::OwnedFd::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+impl From<std::os::unix::net::UnixListener> for UnixListener {
+    fn from(socket: std::os::unix::net::UnixListener) -> UnixListener {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixListener {
+            inner: Inner(socket.into_raw_fd())
+        }
+    }
+}
+
+impl From<UnixListener> for std::os::unix::net::UnixListener {
+    fn from(socket: UnixListener) -> std::os::unix::net::UnixListener {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::net::UnixListener::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+impl<'a> IntoIterator for &'a UnixListener {
+    type Item = io::Result<UnixStream>;
+    type IntoIter = Incoming<'a>;
+
+    fn into_iter(self) -> Incoming<'a> {
+        self.incoming()
+    }
+}
+
+/// A `UnixListener` which only yields connections from peers whose
+/// credentials are accepted by a policy.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::{AuthenticatedListener, UnixListener};
+///
+/// let uid = 1000;
+/// let listener = UnixListener::bind("/path/to/the/socket").unwrap();
+/// let listener = AuthenticatedListener::new(listener, |cred| cred.uid == uid);
+/// for stream in listener {
+///     // only connections from processes running as `uid` get here
+/// }
+/// ```
+pub struct AuthenticatedListener<P> {
+    listener: UnixListener,
+    policy: P,
+}
+
+impl<P> fmt::Debug for AuthenticatedListener<P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "AuthenticatedListener")
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+impl<P: Fn(&UCred) -> bool> AuthenticatedListener<P> {
+    /// Creates a new `AuthenticatedListener` which accepts connections on
+    /// `listener` when `policy` returns `true` for the peer's credentials.
+    pub fn new(listener: UnixListener, policy: P) -> AuthenticatedListener<P> {
+        AuthenticatedListener {
+            listener: listener,
+            policy: policy,
+        }
+    }
+
+    /// Returns a reference to the underlying listener.
+    pub fn listener(&self) -> &UnixListener {
+        &self.listener
+    }
+
+    /// Returns the underlying listener.
+    pub fn into_inner(self) -> UnixListener {
+        self.listener
+    }
+
+    /// Accepts a new incoming connection from a permitted peer.
+    ///
+    /// Connections from peers rejected by the policy are shut down and
+    /// closed, and this keeps waiting for the next connection.
+    pub fn accept(&self) -> io::Result<UnixStream> {
+        loop {
+            let stream = try!(self.listener.accept());
+            let cred = try!(stream.peer_cred());
+            if (self.policy)(&cred) {
+                return Ok(stream);
+            }
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Yields streams from permitted peers, as returned by `accept`.
+///
+/// The iterator will never return `None`.
+impl<P: Fn(&UCred) -> bool> Iterator for AuthenticatedListener<P> {
+    type Item = io::Result<UnixStream>;
+
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        Some(self.accept())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+impl<P> AsRawFd for AuthenticatedListener<P> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+/// A `UnixListener` which removes its socket file when dropped.
+///
+/// The wrapped listener is accessible through `Deref`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::UnixListenerGuard;
+///
+/// let listener = UnixListenerGuard::bind("/path/to/the/socket").unwrap();
+/// for stream in listener.incoming() {
+///     // ...
+/// }
+/// // the socket file is removed here
+/// ```
+#[derive(Debug)]
+pub struct UnixListenerGuard {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixListenerGuard {
+    /// Creates a new `UnixListenerGuard` which will be bound to the specified
+    /// socket.
+    ///
+    /// See `UnixListener::bind` for details.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListenerGuard> {
+        let listener = try!(UnixListener::bind(&path));
+        Ok(UnixListenerGuard {
+            listener: listener,
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Returns the path of the socket file which will be removed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Consumes the guard without removing the socket file, returning the
+    /// listener and the path it was bound to.
+    pub fn into_inner(self) -> (UnixListener, PathBuf) {
+        unsafe {
+            let listener = ptr::read(&self.listener);
+            let path = ptr::read(&self.path);
+            mem::forget(self);
+            (listener, path)
+        }
+    }
+}
+
+impl Deref for UnixListenerGuard {
+    type Target = UnixListener;
+
+    fn deref(&self) -> &UnixListener {
+        &self.listener
+    }
+}
+
+impl Drop for UnixListenerGuard {
+    fn drop(&mut self) {
+        // abstract addresses have no file to remove, so errors are expected
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn is_transient_accept_error(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::Interrupted | io::ErrorKind::ConnectionAborted)
+}
+
+/// An iterator over incoming connections to a `UnixListener`.
+///
+/// It will never return `None`.
+#[derive(Debug)]
+pub struct Incoming<'a> {
+    listener: &'a UnixListener,
+}
+
+impl<'a> Incoming<'a> {
+    /// Converts this iterator into one which ends at the first error.
+    pub fn stop_on_error(self) -> FallibleIncoming<'a> {
+        self.listener.incoming_fallible()
+    }
+}
+
+impl<'a> Iterator for Incoming<'a> {
+    type Item = io::Result<UnixStream>;
+
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        Some(self.listener.accept())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// An iterator over incoming connections to a `UnixListener` and the
+/// addresses they originate from.
+///
+/// It will never return `None`.
+#[derive(Debug)]
+pub struct IncomingWithAddr<'a> {
+    listener: &'a UnixListener,
+}
+
+impl<'a> Iterator for IncomingWithAddr<'a> {
+    type Item = io::Result<(UnixStream, SocketAddr)>;
+
+    fn next(&mut self) -> Option<io::Result<(UnixStream, SocketAddr)>> {
+        Some(self.listener.accept_addr())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// An iterator over incoming connections to a `UnixListener` which ends at
+/// the first error.
+///
+/// Errors such as `EMFILE` would otherwise be returned forever by `Incoming`.
+#[derive(Debug)]
+pub struct FallibleIncoming<'a> {
+    listener: &'a UnixListener,
+    last_error: Option<io::Error>,
+}
+
+impl<'a> FallibleIncoming<'a> {
+    /// Returns the error which ended the iteration, if any.
+    pub fn last_error(&self) -> Option<&io::Error> {
+        self.last_error.as_ref()
+    }
+}
+
+impl<'a> Iterator for FallibleIncoming<'a> {
+    type Item = UnixStream;
+
+    fn next(&mut self) -> Option<UnixStream> {
+        if self.last_error.is_some() {
+            return None;
+        }
+
+        match self.listener.accept() {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                self.last_error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// An iterator over incoming connections to a `UnixListener` from peers
+/// accepted by a predicate on their address.
+///
+/// It will never return `None`.
+pub struct FilteredIncoming<'a, F> {
+    listener: &'a UnixListener,
+    pred: F,
+}
+
+impl<'a, F> fmt::Debug for FilteredIncoming<'a, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "FilteredIncoming")
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+impl<'a, F: Fn(&SocketAddr) -> bool> Iterator for FilteredIncoming<'a, F> {
+    type Item = io::Result<UnixStream>;
+
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok(stream) => stream,
+                Err(e) => return Some(Err(e)),
+            };
+            let addr = stream.peer_addr().unwrap_or_else(|_| SocketAddr::unnamed());
+            if (self.pred)(&addr) {
+                return Some(Ok(stream));
+            }
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// An iterator over incoming connections which owns its `UnixListener`.
+///
+/// It will never return `None`.
+#[derive(Debug)]
+pub struct OwnedIncoming {
+    listener: UnixListener,
+}
+
+impl OwnedIncoming {
+    /// Returns a reference to the underlying listener.
+    pub fn listener(&self) -> &UnixListener {
+        &self.listener
+    }
+}
+
+impl Iterator for OwnedIncoming {
+    type Item = io::Result<UnixStream>;
+
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        Some(self.listener.accept())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+/// A datagram along with its source or destination address, used by
+/// `UnixDatagram::send_many` and `UnixDatagram::recv_many`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatagramMsg {
+    /// The contents of the datagram.
+    pub data: Vec<u8>,
+    /// When sending, the address to send to, or `None` to send to the
+    /// connected peer. When receiving, the address of the sender, or `None`
+    /// if the sender was unnamed.
+    pub addr: Option<SocketAddr>,
+}
+
+// The size of each datagram's slot in the buffer passed to `recv_many`
+fn recv_many_slot(buf: &[u8], max: usize) -> io::Result<usize> {
+    match buf.len() / max {
+        0 => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput,
+                               "buffer is too short to receive that many datagrams"))
+        }
+        slot => Ok(slot),
+    }
+}
+
+impl DatagramMsg {
+    fn received(data: &[u8], addr: SocketAddr) -> DatagramMsg {
+        DatagramMsg {
+            data: data.to_vec(),
+            addr: if addr.address().is_unnamed() { None } else { Some(addr) },
+        }
+    }
+}
+
+/// Information about a datagram received by `UnixDatagram::recv_from_full`.
+#[derive(Debug, Clone)]
+pub struct DatagramBuf {
+    /// The number of bytes copied into the buffer.
+    pub bytes_copied: usize,
+    /// The full size of the datagram, which may exceed `bytes_copied` if the
+    /// buffer was too small.
+    pub true_size: usize,
+    /// The address of the sender.
+    pub addr: SocketAddr,
+}
+
+impl DatagramBuf {
+    /// Returns `true` if the datagram did not fit in the buffer.
+    pub fn is_truncated(&self) -> bool {
+        self.true_size > self.bytes_copied
+    }
+}
+
+/// A message sent by `UnixDatagram::send_meta` or `UnixStream::send_meta`.
+#[derive(Debug, Clone, Copy)]
+pub struct SendMsg<'a> {
+    /// The data to send.
+    pub data: &'a [u8],
+    /// The address to send to, or `None` to send to the connected peer.
+    pub addr: Option<&'a SocketAddr>,
+    /// The control messages to send along with the data.
+    pub ancillary: &'a AncillaryData,
+    /// Flags passed through to `sendmsg(2)`.
+    pub flags: i32,
+}
+
+impl<'a> SendMsg<'a> {
+    fn send(&self, inner: &Inner) -> io::Result<usize> {
+        let addr = self.addr.map(|addr| (&addr.addr, addr.len));
+        inner.send_msg_to(&[io::IoSlice::new(self.data)], addr, self.ancillary, self.flags)
+    }
+}
+
+/// Information about a datagram received by `UnixDatagram::recv_meta`.
+#[derive(Debug, Clone)]
+pub struct RecvMeta {
+    /// The number of bytes copied into the buffer.
+    pub bytes: usize,
+    /// The address of the sender, or `None` if the sender was unnamed.
+    pub addr: Option<SocketAddr>,
+    /// The flags reported by `recvmsg(2)` in `msg_flags`.
+    pub flags: i32,
+    /// The control messages which arrived with the datagram.
+    pub ancillary: AncillaryMessage,
+}
+
+impl RecvMeta {
+    /// Returns `true` if the datagram did not fit in the buffer, as reported
+    /// by `MSG_TRUNC`.
+    pub fn was_truncated(&self) -> bool {
+        self.flags & libc::MSG_TRUNC != 0
+    }
+
+    /// Returns `true` if control messages were discarded because they did
+    /// not fit in the ancillary buffer, as reported by `MSG_CTRUNC`.
+    pub fn control_truncated(&self) -> bool {
+        self.flags & libc::MSG_CTRUNC != 0
+    }
+}
+
+/// Options used to create a `UnixDatagram`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::DatagramOptions;
+///
+/// let socket = DatagramOptions::new()
+///                  .cloexec(true)
+///                  .recv_buf(1024 * 1024)
+///                  .bind("/path/to/my/socket")
+///                  .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DatagramOptions {
+    nonblocking: bool,
+    cloexec: bool,
+    send_buf: Option<usize>,
+    recv_buf: Option<usize>,
+    #[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+    passcred: bool,
+}
+
+impl Default for DatagramOptions {
+    fn default() -> DatagramOptions {
+        DatagramOptions::new()
+    }
+}
+
+impl DatagramOptions {
+    /// Creates a new set of options with the system default buffer sizes and
+    /// all flags disabled.
+    pub fn new() -> DatagramOptions {
+        DatagramOptions {
+            nonblocking: false,
+            cloexec: false,
+            send_buf: None,
+            recv_buf: None,
+            passcred: false,
+        }
+    }
+
+    /// If set, the socket will be in nonblocking mode.
+    pub fn nonblocking(mut self, nonblocking: bool) -> DatagramOptions {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// If set, the close-on-exec flag will be set on the socket.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`.
+    pub fn cloexec(mut self, cloexec: bool) -> DatagramOptions {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Sets the size of the socket's send buffer.
+    ///
+    /// See `UnixDatagram::set_send_buffer_size` for details.
+    pub fn send_buf(mut self, size: usize) -> DatagramOptions {
+        self.send_buf = Some(size);
+        self
+    }
+
+    /// Sets the size of the socket's receive buffer.
+    ///
+    /// See `UnixDatagram::set_recv_buffer_size` for details.
+    pub fn recv_buf(mut self, size: usize) -> DatagramOptions {
+        self.recv_buf = Some(size);
+        self
+    }
+
+    /// If set, the `SO_PASSCRED` option will be enabled on the socket.
+    ///
+    /// Only supported on Linux.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn passcred(mut self, passcred: bool) -> DatagramOptions {
+        self.passcred = passcred;
+        self
+    }
+
+    /// Creates a Unix datagram socket bound to the given path.
+    ///
+    /// As with `UnixDatagram::bind`, a `path` beginning with a null byte is
+    /// interpreted as an abstract address.
+    pub fn bind<P: AsRef<Path>>(self, path: P) -> io::Result<UnixDatagram> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.bind_raw(&addr, len)
+        }
+    }
+
+    /// Creates a Unix datagram socket bound to the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn bind_abstract(self, path: &AbstractPath) -> io::Result<UnixDatagram> {
+        let (addr, len) = path.sockaddr_un();
+        self.bind_raw(&addr, len)
+    }
+
+    /// Creates a Unix datagram socket bound to `addr`.
+    pub fn bind_addr(self, addr: &SocketAddr) -> io::Result<UnixDatagram> {
+        self.bind_raw(&addr.addr, addr.len)
+    }
+
+    /// Creates a Unix datagram socket which is not bound to any address.
+    pub fn unbound(self) -> io::Result<UnixDatagram> {
+        self.socket()
+    }
+
+    fn bind_raw(self, addr: &libc::sockaddr_un, len: libc::socklen_t) -> io::Result<UnixDatagram> {
+        let socket = try!(self.socket());
+        unsafe {
+            try!(cvt_r(|| libc::bind(socket.inner.0, addr as *const _ as *const _, len)));
+        }
+        Ok(socket)
+    }
+
+    fn socket(&self) -> io::Result<UnixDatagram> {
+        let socket = UnixDatagram {
+            inner: try!(Inner::with_cloexec(libc::SOCK_DGRAM, self.cloexec)),
+        };
+        if self.nonblocking {
+            try!(socket.set_nonblocking(true));
+        }
+        if let Some(size) = self.send_buf {
+            try!(socket.set_send_buffer_size(size));
+        }
+        if let Some(size) = self.recv_buf {
+            try!(socket.set_recv_buffer_size(size));
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if self.passcred {
+                try!(socket.inner.set_passcred(true));
+            }
+        }
+        Ok(socket)
+    }
+}
+
+/// A Unix datagram socket.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::UnixDatagram;
+///
+/// let socket = UnixDatagram::bind("/path/to/my/socket").unwrap();
+/// socket.send_to(b"hello world", "/path/to/other/socket").unwrap();
+/// let mut buf = [0; 100];
+/// let (count, address) = socket.recv_from(&mut buf).unwrap();
+/// println!("socket {:?} sent {:?}", address, &buf[..count]);
+/// ```
+pub struct UnixDatagram {
+    inner: Inner,
+}
+
+impl fmt::Debug for UnixDatagram {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = DebugStruct::new(fmt, "UnixDatagram")
+            .field("fd", &self.inner.0);
+        if let Ok(addr) = self.local_addr() {
+            builder = builder.field("local", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            builder = builder.field("peer", &addr);
+        }
+        builder.finish()
+    }
+}
+
+/// Formats the socket's addresses, such as `UnixDatagram { local: /path }`.
+///
+/// The peer address is included if the socket is connected. Addresses which
+/// cannot be retrieved are omitted.
+impl fmt::Display for UnixDatagram {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        display_addrs(fmt,
+                      "UnixDatagram",
+                      &[("local", self.local_addr()), ("peer", self.peer_addr())])
+    }
+}
+
+impl UnixDatagram {
+    /// Creates a Unix datagram socket from the given path.
+    ///
+    /// This is equivalent to `DatagramOptions::new().bind(path)`.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
+        DatagramOptions::new().bind(path)
+    }
+
+    /// Creates a Unix datagram socket from the given path, with the
+    /// close-on-exec flag set on the new socket.
+    ///
+    /// Where supported the flag is set atomically with `SOCK_CLOEXEC`, so the
+    /// socket cannot leak into a child process spawned concurrently by
+    /// another thread.
+    pub fn bind_cloexec<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
+        DatagramOptions::new().cloexec(true).bind(path)
+    }
+
+    /// Creates a Unix datagram socket bound to the abstract address `path`.
+    ///
+    /// Abstract addresses are a nonportable Linux extension.
+    pub fn bind_abstract(path: &AbstractPath) -> io::Result<UnixDatagram> {
+        DatagramOptions::new().bind_abstract(path)
+    }
+
+    /// Creates a Unix datagram socket bound to `addr`.
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixDatagram> {
+        DatagramOptions::new().bind_addr(addr)
+    }
+
+    /// Creates a Unix datagram socket which is not bound to any address.
+    ///
+    /// The socket can be used to send datagrams with `send_to`, and will be
+    /// reported by `local_addr` as having an unnamed address.
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        DatagramOptions::new().unbound()
+    }
+
+    /// Connects the socket to the specified address.
+    ///
+    /// The `send` method may be used to send data to the specified address.
+    /// `recv` and `recv_from` will only receive data from that address.
+    ///
+    /// Calling `connect` again replaces the previously connected address.
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+
+            self.inner.connect(&addr as *const _ as *const _, len)
+        }
+    }
+
+    /// Dissolves the connection established by `connect`.
+    ///
+    /// This connects the socket to an address of family `AF_UNSPEC`.
+    /// Afterwards `send_to` may be used with any address again, and `recv`
+    /// will receive data from any sender.
+    pub fn disconnect(&self) -> io::Result<()> {
+        unsafe {
+            let mut addr: libc::sockaddr = mem::zeroed();
+            addr.sa_family = libc::AF_UNSPEC as libc::sa_family_t;
+            let len = mem::size_of::<libc::sa_family_t>() as libc::socklen_t;
+
+            // dissolving a connection never blocks
+            cvt(libc::connect(self.inner.0, &addr, len)).map(|_| ())
+        }
+    }
+
+    /// Create an unnamed pair of connected sockets.
+    ///
+    /// Returns two `UnixDatagram`s which are connected to each other.
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (i1, i2) = try!(Inner::new_pair(libc::SOCK_DGRAM));
+        Ok((UnixDatagram { inner: i1 }, UnixDatagram { inner: i2 }))
+    }
+
+    /// Converts a standard library `UnixDatagram` into this crate's type.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn from_std(socket: std::os::unix::net::UnixDatagram) -> UnixDatagram {
+        UnixDatagram::from(socket)
+    }
+
+    /// Converts the socket into a standard library `UnixDatagram`.
+    ///
+    /// This is the same as the `From` implementation.
+    pub fn into_std(self) -> std::os::unix::net::UnixDatagram {
+        self.into()
+    }
+
+    /// Returns the address of this socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+    }
+
+    /// Returns the address of this socket's peer.
+    ///
+    /// The `connect` method will connect the socket to a peer.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getpeername(self.inner.0, addr, len) })
+    }
+
+    /// Determines whether the socket is connected to a peer.
+    ///
+    /// This is the case when `peer_addr` succeeds; an `ENOTCONN` error from
+    /// it means the socket is not connected, and other errors are returned.
+    pub fn is_connected(&self) -> io::Result<bool> {
+        match self.peer_addr() {
+            Ok(_) => Ok(true),
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOTCONN) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receives data from the socket.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf, 0)
+    }
+
+    /// Receives data from the socket, returning `None` as the address if the
+    /// sender is unnamed.
+    ///
+    /// Datagrams sent from unbound sockets carry no address. `recv_from`
+    /// reports their address as unnamed, while this makes the case explicit.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came, if any.
+    pub fn recv_from_opt(&self, buf: &mut [u8]) -> io::Result<(usize, Option<SocketAddr>)> {
+        let (count, addr) = try!(self.recv_from(buf));
+        if addr.address().is_unnamed() {
+            Ok((count, None))
+        } else {
+            Ok((count, Some(addr)))
+        }
+    }
+
+    /// Receives data from the socket, returning a `TimedOut` error if none
+    /// arrives before `deadline`.
+    ///
+    /// The wait is done with `poll`, so unlike `set_read_timeout` this does
+    /// not change any socket options, and is safe to use concurrently with
+    /// other calls on the socket.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came.
+    pub fn recv_from_deadline(&self,
+                              buf: &mut [u8],
+                              deadline: std::time::Instant)
+                              -> io::Result<(usize, SocketAddr)> {
+        loop {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            try!(self.inner.poll(libc::POLLIN, timeout));
+            match self.inner.recv_from(buf, libc::MSG_DONTWAIT) {
+                // another reader took the datagram first
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Receives data from the socket without removing it from the queue.
+    ///
+    /// The next call to `recv_from` or `recv` will return the same datagram
+    /// again. On success, returns the number of bytes read and the address
+    /// from whence the data came.
+    pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf, libc::MSG_PEEK)
+    }
+
+    /// Receives data from the socket, reporting the full size of the
+    /// datagram even if it did not fit in `buf`.
+    ///
+    /// On Linux this uses `MSG_TRUNC`. Other platforms do not report the
+    /// full size, so `true_size` is always equal to `bytes_copied` there.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recv_from_full(&self, buf: &mut [u8]) -> io::Result<DatagramBuf> {
+        let (count, addr) = try!(self.inner.recv_from(buf, libc::MSG_TRUNC));
+        Ok(DatagramBuf {
+            bytes_copied: cmp::min(count, buf.len()),
+            true_size: count,
+            addr: addr,
+        })
+    }
+
+    /// Receives data from the socket, reporting the full size of the
+    /// datagram even if it did not fit in `buf`.
+    ///
+    /// On Linux this uses `MSG_TRUNC`. Other platforms do not report the
+    /// full size, so `true_size` is always equal to `bytes_copied` there.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn recv_from_full(&self, buf: &mut [u8]) -> io::Result<DatagramBuf> {
+        let (count, addr) = try!(self.inner.recv_from(buf, 0));
+        Ok(DatagramBuf {
+            bytes_copied: count,
+            true_size: count,
+            addr: addr,
+        })
+    }
+
+    /// Sends data on the socket to the given address.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
+        self.send_to_with_flags(buf, path, 0)
+    }
+
+    /// Receives data from the socket, passing `flags` through to
+    /// `recvfrom(2)`.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came.
+    pub fn recv_from_with_flags(&self,
+                                buf: &mut [u8],
+                                flags: libc::c_int)
+                                -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf, flags)
+    }
+
+    /// Sends data on the socket to the given address, passing `flags` through
+    /// to `sendto(2)`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_to_with_flags<P: AsRef<Path>>(&self,
+                                              buf: &[u8],
+                                              path: P,
+                                              flags: libc::c_int)
+                                              -> io::Result<usize> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.inner.send_to(buf, &addr, len, flags)
+        }
+    }
+
+    /// Sends data on the socket to the given abstract address.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_to_abstract(&self, buf: &[u8], path: &AbstractPath) -> io::Result<usize> {
+        let (addr, len) = path.sockaddr_un();
+        self.inner.send_to(buf, &addr, len, 0)
+    }
+
+    /// Sends data on the socket to `addr`, such as one returned by
+    /// `recv_from`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_to_addr(&self, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, &addr.addr, addr.len, 0)
+    }
+
+    /// Receives data from the socket's peer.
+    ///
+    /// The `connect` method will connect this socket to a peer. On success,
+    /// returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, 0)
+    }
+
+    /// Sends data on the socket to the socket's peer.
+    ///
+    /// The peer address may be set by the `connect` method, and this method
+    /// will return an error if the socket has not already been connected.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, 0)
+    }
+
+    /// Receives a datagram from the socket's peer, scattering it across
+    /// `bufs`.
+    ///
+    /// On success, returns the total number of bytes read.
+    pub fn recv_vectored(&self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        self.inner.recv_msg(bufs, &mut AncillaryMessage::with_capacity(0))
+    }
+
+    /// Receives a datagram from the socket, scattering it across `bufs` in
+    /// order.
+    ///
+    /// Bytes which do not fit in `bufs` are discarded, as with `recv_from`.
+    ///
+    /// On success, returns the total number of bytes read and the address
+    /// from whence the data came.
+    pub fn recv_vectored_from(&self,
+                              bufs: &mut [io::IoSliceMut])
+                              -> io::Result<(usize, SocketAddr)> {
+        let mut ancillary = AncillaryMessage::with_capacity(0);
+        let mut addr = SocketAddr::unnamed();
+        let (count, _) = try!(self.inner.recv_msg_from(bufs, Some(&mut addr), &mut ancillary, 0));
+        Ok((count, addr))
+    }
+
+    /// Like `recv_vectored`, but returns an error of kind `WouldBlock` if no
+    /// datagram is queued, as with `recv_nonblocking`.
+    pub fn try_recv_vectored(&self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        let mut ancillary = AncillaryMessage::with_capacity(0);
+        let (count, _) = try!(self.inner.recv_msg_from(bufs, None, &mut ancillary,
+                                                       libc::MSG_DONTWAIT));
+        Ok(count)
+    }
+
+    /// Sends a single datagram gathered from `bufs` to the socket's peer.
+    ///
+    /// On success, returns the total number of bytes written.
+    pub fn send_vectored(&self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        self.inner.send_msg(bufs, &AncillaryData::new())
+    }
+
+    /// Sends a single datagram gathered from `bufs` to the given address.
+    ///
+    /// On success, returns the total number of bytes written.
+    pub fn send_vectored_to<P: AsRef<Path>>(&self,
+                                            bufs: &[io::IoSlice],
+                                            path: P)
+                                            -> io::Result<usize> {
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.inner.send_msg_to(bufs, Some((&addr, len)), &AncillaryData::new(), 0)
+        }
+    }
+
+    /// Sends a single datagram gathered from `bufs` to the given address.
+    ///
+    /// On success, returns the total number of bytes written.
+    pub fn send_vectored_to_addr(&self,
+                                 bufs: &[io::IoSlice],
+                                 addr: &SocketAddr)
+                                 -> io::Result<usize> {
+        self.inner.send_msg_to(bufs, Some((&addr.addr, addr.len)), &AncillaryData::new(), 0)
+    }
+
+    /// Receives up to `max` datagrams, appending them to `messages`.
+    ///
+    /// `buf` is divided into `max` equal slots, each receiving one datagram;
+    /// datagrams longer than a slot are truncated. The buffer is supplied by
+    /// the caller, who knows how long the expected datagrams are, and can be
+    /// reused across calls to avoid allocating it each time. `max` is capped
+    /// at 1024, and an `InvalidInput` error is returned if `buf` is too short
+    /// to provide a slot for each datagram.
+    ///
+    /// This blocks until at least one datagram is available and then returns
+    /// as many as are queued without blocking further. On Linux all
+    /// datagrams are received with a single `recvmmsg` call.
+    ///
+    /// On success, returns the number of datagrams received.
+    #[cfg(target_os = "linux")]
+    pub fn recv_many(&self,
+                     messages: &mut Vec<DatagramMsg>,
+                     buf: &mut [u8],
+                     max: usize)
+                     -> io::Result<usize> {
+        let max = calc_iovlen(max);
+        if max == 0 {
+            return Ok(0);
+        }
+        let slot = try!(recv_many_slot(buf, max));
+
+        unsafe {
+            let mut addrs: Vec<libc::sockaddr_un> = vec![mem::zeroed(); max];
+            let mut iovs: Vec<libc::iovec> = buf.chunks_mut(slot)
+                .take(max)
+                .map(|buf| {
+                    libc::iovec {
+                        iov_base: buf.as_mut_ptr() as *mut _,
+                        iov_len: buf.len(),
+                    }
+                })
+                .collect();
+            let mut hdrs: Vec<libc::mmsghdr> = iovs.iter_mut()
+                .zip(addrs.iter_mut())
+                .map(|(iov, addr)| {
+                    let mut hdr: libc::mmsghdr = mem::zeroed();
+                    hdr.msg_hdr.msg_name = addr as *mut _ as *mut _;
+                    hdr.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_un>() as _;
+                    hdr.msg_hdr.msg_iov = iov;
+                    hdr.msg_hdr.msg_iovlen = 1;
+                    hdr
+                })
+                .collect();
+
+            let count = try!(cvt(libc::recvmmsg(self.inner.0,
+                                                hdrs.as_mut_ptr(),
+                                                max as _,
+                                                libc::MSG_WAITFORONE as _,
+                                                ptr::null_mut())));
+
+            let received = hdrs.iter().zip(addrs.iter()).zip(buf.chunks(slot));
+            for ((hdr, addr), buf) in received.take(count as usize) {
+                let addr = try!(SocketAddr::new(|ptr, len| {
+                    *(ptr as *mut libc::sockaddr_un) = *addr;
+                    *len = hdr.msg_hdr.msg_namelen;
+                    0
+                }));
+                let len = cmp::min(hdr.msg_len as usize, buf.len());
+                messages.push(DatagramMsg::received(&buf[..len], addr));
+            }
+            Ok(count as usize)
+        }
+    }
+
+    /// Receives up to `max` datagrams, appending them to `messages`.
+    ///
+    /// `buf` is divided into `max` equal slots, each receiving one datagram;
+    /// datagrams longer than a slot are truncated. The buffer is supplied by
+    /// the caller, who knows how long the expected datagrams are, and can be
+    /// reused across calls to avoid allocating it each time. `max` is capped
+    /// at 1024, and an `InvalidInput` error is returned if `buf` is too short
+    /// to provide a slot for each datagram.
+    ///
+    /// This blocks until at least one datagram is available and then returns
+    /// as many as are queued without blocking further. On Linux all
+    /// datagrams are received with a single `recvmmsg` call.
+    ///
+    /// On success, returns the number of datagrams received.
+    #[cfg(not(target_os = "linux"))]
+    pub fn recv_many(&self,
+                     messages: &mut Vec<DatagramMsg>,
+                     buf: &mut [u8],
+                     max: usize)
+                     -> io::Result<usize> {
+        let max = calc_iovlen(max);
+        if max == 0 {
+            return Ok(0);
+        }
+        let slot = try!(recv_many_slot(buf, max));
+
+        let mut count = 0;
+        for buf in buf.chunks_mut(slot).take(max) {
+            let flags = if count == 0 { 0 } else { libc::MSG_DONTWAIT };
+            match self.inner.recv_from(buf, flags) {
+                Ok((len, addr)) => {
+                    messages.push(DatagramMsg::received(&buf[..len], addr));
+                    count += 1;
+                }
+                Err(ref e) if count > 0 && e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Sends each of `messages` as a separate datagram.
+    ///
+    /// On Linux all datagrams are sent with a single `sendmmsg` call. If an
+    /// error occurs after some datagrams have been sent, the number sent so
+    /// far is returned and the error is discarded.
+    ///
+    /// On success, returns the number of datagrams sent.
+    #[cfg(target_os = "linux")]
+    pub fn send_many(&self, messages: &[DatagramMsg]) -> io::Result<usize> {
+        let messages = &messages[..calc_iovlen(messages.len())];
+        if messages.is_empty() {
+            return Ok(0);
+        }
+
+        unsafe {
+            let mut iovs: Vec<libc::iovec> = messages.iter()
+                .map(|msg| {
+                    libc::iovec {
+                        iov_base: msg.data.as_ptr() as *mut _,
+                        iov_len: msg.data.len(),
+                    }
+                })
+                .collect();
+            let mut hdrs: Vec<libc::mmsghdr> = iovs.iter_mut()
+                .zip(messages.iter())
+                .map(|(iov, msg)| {
+                    let mut hdr: libc::mmsghdr = mem::zeroed();
+                    if let Some(ref addr) = msg.addr {
+                        hdr.msg_hdr.msg_name = &addr.addr as *const _ as *mut _;
+                        hdr.msg_hdr.msg_namelen = addr.len;
+                    }
+                    hdr.msg_hdr.msg_iov = iov;
+                    hdr.msg_hdr.msg_iovlen = 1;
+                    hdr
+                })
+                .collect();
+
+            let count = try!(cvt(libc::sendmmsg(self.inner.0,
+                                                hdrs.as_mut_ptr(),
+                                                hdrs.len() as _,
+                                                SEND_NOSIGNAL as _)));
+            Ok(count as usize)
+        }
+    }
+
+    /// Sends each of `messages` as a separate datagram.
+    ///
+    /// On Linux all datagrams are sent with a single `sendmmsg` call. If an
+    /// error occurs after some datagrams have been sent, the number sent so
+    /// far is returned and the error is discarded.
+    ///
+    /// On success, returns the number of datagrams sent.
+    #[cfg(not(target_os = "linux"))]
+    pub fn send_many(&self, messages: &[DatagramMsg]) -> io::Result<usize> {
+        for (count, msg) in messages.iter().enumerate() {
+            let res = match msg.addr {
+                Some(ref addr) => self.inner.send_to(&msg.data, &addr.addr, addr.len, 0),
+                None => self.inner.send(&msg.data, 0),
+            };
+            match res {
+                Ok(_) => {}
+                Err(_) if count > 0 => return Ok(count),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(messages.len())
+    }
+
+    /// Receives data from the socket's peer without blocking.
+    ///
+    /// This behaves as though the socket were in nonblocking mode for this
+    /// call only: if no datagram is queued, an error of kind `WouldBlock` is
+    /// returned. The mode set by `set_nonblocking` is left untouched.
+    pub fn recv_nonblocking(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, libc::MSG_DONTWAIT)
+    }
+
+    /// Sends data on the socket to the socket's peer without blocking.
+    ///
+    /// If the datagram cannot be queued immediately, an error of kind
+    /// `WouldBlock` is returned. The mode set by `set_nonblocking` is left
+    /// untouched.
+    pub fn send_nonblocking(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, libc::MSG_DONTWAIT)
+    }
+
+    /// Sets the read timeout for the socket.
+    ///
+    /// If the provided value is `None`, then `recv_from` calls will block
+    /// indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.inner.set_timeout(timeout, libc::SO_RCVTIMEO)
+    }
+
+    /// Sets the write timeout for the socket.
+    ///
+    /// If the provided value is `None`, then `send_to` calls will block
+    /// indefinitely. It is an error to pass the zero `Duration` to this
+    /// method.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.inner.set_timeout(timeout, libc::SO_SNDTIMEO)
+    }
+
+    /// Returns the read timeout of this socket.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn read_timeout(&self) -> io::Result<Option<std::time::Duration>> {
+        self.inner.timeout(libc::SO_RCVTIMEO)
+    }
+
+    /// Returns the write timeout of this socket.
+    ///
+    /// Requires the `socket_timeout` feature.
+    #[cfg(feature = "socket_timeout")]
+    pub fn write_timeout(&self) -> io::Result<Option<std::time::Duration>> {
+        self.inner.timeout(libc::SO_SNDTIMEO)
+    }
+
+    /// Sends data on the socket to the socket's peer along with the control
+    /// messages in `ancillary`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_with_ancillary(&self, buf: &[u8], ancillary: &AncillaryData) -> io::Result<usize> {
+        self.inner.send_msg(&[io::IoSlice::new(buf)], ancillary)
+    }
+
+    /// Receives data from the socket, storing any control messages that
+    /// arrive with it in `ancillary`.
+    ///
+    /// Control messages which do not fit in the capacity of `ancillary` are
+    /// discarded, which `AncillaryMessage::is_truncated` will report.
+    ///
+    /// On success, returns the number of bytes read.
+    pub fn recv_with_ancillary(&self,
+                               buf: &mut [u8],
+                               ancillary: &mut AncillaryMessage)
+                               -> io::Result<usize> {
+        self.inner.recv_msg(&mut [io::IoSliceMut::new(buf)], ancillary)
+    }
+
+    /// Sends a datagram with the address, control messages and flags in
+    /// `msg`, in a single call to `sendmsg(2)`.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_meta(&self, msg: &SendMsg) -> io::Result<usize> {
+        msg.send(&self.inner)
+    }
+
+    /// Receives a datagram from the socket along with its source address,
+    /// the control messages that arrive with it, and the flags reported by
+    /// `recvmsg(2)`, all in a single call.
+    ///
+    /// Control messages are returned in `RecvMeta::ancillary`, in a buffer
+    /// of `ancillary_capacity` bytes; any which do not fit are discarded, as
+    /// `control_truncated` will report.
+    pub fn recv_meta(&self, buf: &mut [u8], ancillary_capacity: usize) -> io::Result<RecvMeta> {
+        let mut addr = SocketAddr::unnamed();
+        let mut ancillary = AncillaryMessage::with_capacity(ancillary_capacity);
+        let (count, flags) = try!(self.inner.recv_msg_from(&mut [io::IoSliceMut::new(buf)],
+                                                           Some(&mut addr),
+                                                           &mut ancillary,
+                                                           0));
+        Ok(RecvMeta {
+            bytes: count,
+            addr: if addr.address().is_unnamed() { None } else { Some(addr) },
+            flags: flags,
+            ancillary: ancillary,
+        })
+    }
+
+    /// Enables or disables the `SO_TIMESTAMP` option.
+    ///
+    /// When enabled, the time at which each datagram was received is
+    /// attached to it as an `SCM_TIMESTAMP` control message, which can be
+    /// read with `recv_from_timestamp`.
+    pub fn set_timestamp(&self, timestamp: bool) -> io::Result<()> {
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_TIMESTAMP, timestamp as libc::c_int)
+    }
+
+    /// Receives data from the socket along with the time at which the kernel
+    /// received it.
+    ///
+    /// The timestamp is only available if `set_timestamp`, or on Linux
+    /// `LinuxUnixDatagramExt::set_timestamp_ns`, has been enabled, and is
+    /// `None` otherwise. It has nanosecond resolution when it was enabled
+    /// with `set_timestamp_ns`.
+    ///
+    /// On success, returns the number of bytes read, the address from whence
+    /// the data came, and the timestamp.
+    pub fn recv_from_timestamp(&self,
+                               buf: &mut [u8])
+                               -> io::Result<(usize, SocketAddr, Option<std::time::SystemTime>)> {
+        let size = unsafe { libc::CMSG_SPACE(mem::size_of::<libc::timespec>() as _) as usize };
+        let mut ancillary = AncillaryMessage::with_capacity(size);
+        let mut addr = SocketAddr::unnamed();
+        let (count, _) = try!(self.inner.recv_msg_from(&mut [io::IoSliceMut::new(buf)],
+                                                       Some(&mut addr),
+                                                       &mut ancillary,
+                                                       0));
+        let timestamp = ancillary.entries().filter_map(|entry| {
+            match entry {
+                CmsgEntry::Timestamp(time) => Some(time),
+                _ => None,
+            }
+        }).next();
+        Ok((count, addr, timestamp))
+    }
+
+    /// Returns the size of the next pending datagram, using the `FIONREAD`
+    /// ioctl.
+    ///
+    /// This can be used to size a buffer to receive the datagram exactly. On
+    /// some platforms, including macOS, the total size of all pending
+    /// datagrams is returned instead.
+    pub fn available_bytes(&self) -> io::Result<usize> {
+        self.inner.available_bytes()
+    }
+
+    /// Sets the size of the socket's send buffer via `SO_SNDBUF`.
+    ///
+    /// The kernel may adjust the requested size; Linux doubles it to leave
+    /// room for bookkeeping overhead and clamps it to `net.core.wmem_max`, so
+    /// `send_buffer_size` will generally not return the value set here.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.inner.set_buffer_size(libc::SO_SNDBUF, size)
+    }
+
+    /// Returns the size of the socket's send buffer.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        self.inner.buffer_size(libc::SO_SNDBUF)
+    }
+
+    /// Sets the size of the socket's receive buffer via `SO_RCVBUF`.
+    ///
+    /// As with `set_send_buffer_size`, the kernel may adjust the requested
+    /// size.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        self.inner.set_buffer_size(libc::SO_RCVBUF, size)
+    }
+
+    /// Returns the size of the socket's receive buffer.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        self.inner.buffer_size(libc::SO_RCVBUF)
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// This will retrieve the pending error on the socket, if any, clearing
+    /// it in the process.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// While in nonblocking mode, `recv_from` and `send_to` calls which would otherwise block
+    /// will instead return an error of kind `WouldBlock`.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Returns whether the socket is in nonblocking mode.
+    pub fn nonblocking(&self) -> io::Result<bool> {
+        self.inner.nonblocking()
+    }
+
+    /// Shut down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O calls on the
+    /// specified portions to immediately return with an appropriate value
+    /// (see the documentation of `Shutdown`).
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Shut down the receiving side of this socket.
+    ///
+    /// Once shut down, `recv` returns 0 instead of waiting for a datagram.
+    /// This is equivalent to `shutdown(Shutdown::Read)`.
+    pub fn shutdown_read(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Read)
+    }
+
+    /// Shut down the sending side of this socket.
+    ///
+    /// Once shut down, sends fail with a `BrokenPipe` error. This is
+    /// equivalent to `shutdown(Shutdown::Write)`.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Write)
+    }
+
+    /// Returns whether a receive from the socket would return without
+    /// blocking.
+    ///
+    /// This polls the socket for `POLLIN` with a zero timeout. A socket is
+    /// readable when a datagram is queued, when its receiving side has been
+    /// shut down, or when it has a pending error.
+    pub fn is_readable(&self) -> io::Result<bool> {
+        self.inner.is_readable()
+    }
+
+    /// Returns whether a send from the socket would return without blocking.
+    ///
+    /// This polls the socket for `POLLOUT` with a zero timeout. A connected
+    /// socket whose peer's receive queue is full is not writable. A socket
+    /// with a pending error is also considered writable.
+    pub fn is_writable(&self) -> io::Result<bool> {
+        self.inner.is_writable()
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+#[cfg(feature = "from_raw_fd")]
+/// Requires the `from_raw_fd` feature.
+impl std::os::unix::io::FromRawFd for UnixDatagram {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixDatagram {
+        UnixDatagram {
+            inner: Inner(fd)
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl std::os::unix::io::AsFd for UnixDatagram {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.0) }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<std::os::unix::io::OwnedFd> for UnixDatagram {
+    fn from(fd: std::os::unix::io::OwnedFd) -> UnixDatagram {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixDatagram {
+            inner: Inner(fd.into_raw_fd())
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<UnixDatagram> for std::os::unix::io::OwnedFd {
+    fn from(socket: UnixDatagram) -> std::os::unix::io::OwnedFd {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::io::OwnedFd::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+impl From<std::os::unix::net::UnixDatagram> for UnixDatagram {
+    fn from(socket: std::os::unix::net::UnixDatagram) -> UnixDatagram {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixDatagram {
+            inner: Inner(socket.into_raw_fd())
+        }
+    }
+}
+
+impl From<UnixDatagram> for std::os::unix::net::UnixDatagram {
+    fn from(socket: UnixDatagram) -> std::os::unix::net::UnixDatagram {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::net::UnixDatagram::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+/// A Unix sequenced packet socket.
+///
+/// Sequenced packet sockets are connection oriented like stream sockets, but
+/// preserve message boundaries like datagram sockets.
+///
+/// Sequenced packet sockets are not supported on all platforms, notably OSX.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::UnixSeqpacket;
+///
+/// let socket = UnixSeqpacket::connect("/path/to/my/socket").unwrap();
+/// socket.send(b"hello world").unwrap();
+/// let mut buf = [0; 100];
+/// let count = socket.recv(&mut buf).unwrap();
+/// println!("received {:?}", &buf[..count]);
+/// ```
+pub struct UnixSeqpacket {
+    inner: Inner,
+}
+
+/// The result of `UnixSeqpacket::recv_msg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvMsg {
+    /// A message of the contained length was received in full.
+    Message(usize),
+    /// A message was received but did not fit in the buffer. The contained
+    /// number of bytes were read, and the rest of the message was discarded.
+    Truncated(usize),
+    /// The peer has shut down the connection, or sent an empty message.
+    Eof,
+}
+
+impl fmt::Debug for UnixSeqpacket {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = DebugStruct::new(fmt, "UnixSeqpacket")
+            .field("fd", &self.inner.0);
+        if let Ok(addr) = self.local_addr() {
+            builder = builder.field("local", &addr);
+        }
+        if let Ok(addr) = self.peer_addr() {
+            builder = builder.field("peer", &addr);
+        }
+        builder.finish()
+    }
+}
+
+impl UnixSeqpacket {
+    /// Connect to the socket named by `path`.
+    ///
+    /// Linux provides, as a nonportable extension, a separate "abstract"
+    /// address namespace as opposed to filesystem-based addressing. If `path`
+    /// begins with a null byte, it will be interpreted as an "abstract"
+    /// address. Otherwise, it will be interpreted as a "pathname" address,
+    /// corresponding to a path on the filesystem.
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixSeqpacket> {
+        unsafe {
+            let inner = try!(Inner::new(libc::SOCK_SEQPACKET));
+            let (addr, len) = try!(sockaddr_un(path));
+
+            try!(inner.connect(&addr as *const _ as *const _, len));
+
+            Ok(UnixSeqpacket {
+                inner: inner,
+            })
+        }
+    }
+
+    /// Create an unnamed pair of connected sockets.
+    ///
+    /// Returns two `UnixSeqpacket`s which are connected to each other.
+    pub fn pair() -> io::Result<(UnixSeqpacket, UnixSeqpacket)> {
+        let (i1, i2) = try!(Inner::new_pair(libc::SOCK_SEQPACKET));
+        Ok((UnixSeqpacket { inner: i1 }, UnixSeqpacket { inner: i2 }))
+    }
+
+    /// Create a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixSeqpacket` is a reference to the same socket that
+    /// this object references. Both handles will send and receive on the same
+    /// connection, and options set on one socket will be propogated to the
+    /// other.
+    pub fn try_clone(&self) -> io::Result<UnixSeqpacket> {
+        Ok(UnixSeqpacket {
+            inner: try!(self.inner.try_clone())
+        })
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+    }
+
+    /// Returns the socket address of the remote half of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getpeername(self.inner.0, addr, len) })
+    }
+
+    /// Receives a single message from the socket.
+    ///
+    /// If the message is larger than `buf`, the excess bytes are discarded.
+    /// Returns `0` once the peer has shut down the connection.
+    ///
+    /// On success, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf, 0)
+    }
+
+    /// Receives a single message from the socket, reporting whether it was
+    /// truncated.
+    ///
+    /// If the message is larger than `buf`, the excess bytes are discarded
+    /// and `RecvMsg::Truncated` is returned.
+    ///
+    /// The OS reports an empty message the same way as the peer shutting
+    /// down the connection, so both are returned as `RecvMsg::Eof`. Protocols
+    /// using this method should not send empty messages.
+    pub fn recv_msg(&self, buf: &mut [u8]) -> io::Result<RecvMsg> {
+        let mut ancillary = AncillaryMessage::with_capacity(0);
+        let (count, flags) = try!(self.inner.recv_msg_from(&mut [io::IoSliceMut::new(buf)],
+                                                           None,
+                                                           &mut ancillary,
+                                                           0));
+        if flags & libc::MSG_TRUNC != 0 {
+            Ok(RecvMsg::Truncated(count))
+        } else if count == 0 {
+            Ok(RecvMsg::Eof)
+        } else {
+            Ok(RecvMsg::Message(count))
+        }
+    }
+
+    /// Sends `buf` as a single message on the socket.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf, 0)
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// This will retrieve the pending error on the socket, if any, clearing
+    /// it in the process.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// While in nonblocking mode, `send` and `recv` calls which would
+    /// otherwise block will instead return an error of kind `WouldBlock`.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Returns whether the socket is in nonblocking mode.
+    pub fn nonblocking(&self) -> io::Result<bool> {
+        self.inner.nonblocking()
+    }
+
+    /// Shut down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O calls on the
+    /// specified portions to immediately return with an appropriate value
+    /// (see the documentation of `Shutdown`).
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Shut down the read half of this connection.
+    ///
+    /// This is equivalent to `shutdown(Shutdown::Read)`.
+    pub fn shutdown_read(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Read)
+    }
+
+    /// Shut down the write half of this connection.
+    ///
+    /// This is equivalent to `shutdown(Shutdown::Write)`.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Write)
+    }
+
+    /// Returns whether a receive from the socket would return without
+    /// blocking.
+    ///
+    /// This polls the socket for `POLLIN` with a zero timeout. A socket is
+    /// readable when a packet is queued, when the peer has closed the
+    /// connection, or when it has a pending error.
+    pub fn is_readable(&self) -> io::Result<bool> {
+        self.inner.is_readable()
+    }
+
+    /// Returns whether a send from the socket would return without blocking.
+    ///
+    /// This polls the socket for `POLLOUT` with a zero timeout. A socket
+    /// whose peer has hung up or with a pending error is also considered
+    /// writable.
+    pub fn is_writable(&self) -> io::Result<bool> {
+        self.inner.is_writable()
+    }
+}
+
+impl AsRawFd for UnixSeqpacket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+#[cfg(feature = "from_raw_fd")]
+/// Requires the `from_raw_fd` feature.
+impl std::os::unix::io::FromRawFd for UnixSeqpacket {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixSeqpacket {
+        UnixSeqpacket {
+            inner: Inner(fd)
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl std::os::unix::io::AsFd for UnixSeqpacket {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.0) }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<std::os::unix::io::OwnedFd> for UnixSeqpacket {
+    fn from(fd: std::os::unix::io::OwnedFd) -> UnixSeqpacket {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixSeqpacket {
+            inner: Inner(fd.into_raw_fd())
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<UnixSeqpacket> for std::os::unix::io::OwnedFd {
+    fn from(socket: UnixSeqpacket) -> std::os::unix::io::OwnedFd {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::io::OwnedFd::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+/// A structure representing a Unix sequenced packet socket server.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use unix_socket::UnixSeqpacketListener;
+///
+/// let listener = UnixSeqpacketListener::bind("/path/to/the/socket").unwrap();
+///
+/// for socket in listener.incoming() {
+///     let socket = socket.unwrap();
+///     let mut buf = [0; 100];
+///     let count = socket.recv(&mut buf).unwrap();
+///     socket.send(&buf[..count]).unwrap();
+/// }
+/// ```
+pub struct UnixSeqpacketListener {
+    inner: Inner,
+}
+
+impl fmt::Debug for UnixSeqpacketListener {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = DebugStruct::new(fmt, "UnixSeqpacketListener")
+            .field("fd", &self.inner.0);
+        if let Ok(addr) = self.local_addr() {
+            builder = builder.field("local", &addr);
+        }
+        builder.finish()
+    }
+}
+
+impl UnixSeqpacketListener {
+    /// Creates a new `UnixSeqpacketListener` which will be bound to the
+    /// specified socket.
+    ///
+    /// Linux provides, as a nonportable extension, a separate "abstract"
+    /// address namespace as opposed to filesystem-based addressing. If `path`
+    /// begins with a null byte, it will be interpreted as an "abstract"
+    /// address. Otherwise, it will be interpreted as a "pathname" address,
+    /// corresponding to a path on the filesystem.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixSeqpacketListener> {
+        unsafe {
+            let inner = try!(Inner::new(libc::SOCK_SEQPACKET));
+            let (addr, len) = try!(sockaddr_un(path));
+
+            try!(cvt_r(|| libc::bind(inner.0, &addr as *const _ as *const _, len)));
+            try!(cvt_r(|| libc::listen(inner.0, 128)));
+
+            Ok(UnixSeqpacketListener {
+                inner: inner,
+            })
+        }
+    }
+
+    /// Accepts a new incoming connection to this listener.
+    ///
+    /// The close-on-exec flag is set on the returned socket.
+    pub fn accept(&self) -> io::Result<UnixSeqpacket> {
+        unsafe {
+            self.inner.accept(ptr::null_mut(), ptr::null_mut(), false)
+                .map(|inner| UnixSeqpacket { inner: inner })
+        }
+    }
+
+    /// Create a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixSeqpacketListener` is a reference to the same socket
+    /// that this object references. Both handles can be used to accept
+    /// incoming connections and options set on one listener will affect the
+    /// other.
+    pub fn try_clone(&self) -> io::Result<UnixSeqpacketListener> {
+        Ok(UnixSeqpacketListener {
+            inner: try!(self.inner.try_clone())
+        })
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe { libc::getsockname(self.inner.0, addr, len) })
+    }
+
+    /// Returns the value of the `SO_ERROR` option.
+    ///
+    /// This will retrieve the pending error on the socket, if any, clearing
+    /// it in the process.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// While in nonblocking mode, `accept` calls which would otherwise block
+    /// will instead return an error of kind `WouldBlock`.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Returns whether the socket is in nonblocking mode.
+    pub fn nonblocking(&self) -> io::Result<bool> {
+        self.inner.nonblocking()
+    }
+
+    /// Returns an iterator over incoming connections.
+    ///
+    /// The iterator will never return `None`.
+    pub fn incoming<'a>(&'a self) -> SeqpacketIncoming<'a> {
+        SeqpacketIncoming {
+            listener: self
+        }
+    }
+}
+
+impl AsRawFd for UnixSeqpacketListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.0
+    }
+}
+
+#[cfg(feature = "from_raw_fd")]
+/// Requires the `from_raw_fd` feature.
+impl std::os::unix::io::FromRawFd for UnixSeqpacketListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixSeqpacketListener {
+        UnixSeqpacketListener {
+            inner: Inner(fd)
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl std::os::unix::io::AsFd for UnixSeqpacketListener {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.0) }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<std::os::unix::io::OwnedFd> for UnixSeqpacketListener {
+    fn from(fd: std::os::unix::io::OwnedFd) -> UnixSeqpacketListener {
+        use std::os::unix::io::IntoRawFd;
+
+        UnixSeqpacketListener {
+            inner: Inner(fd.into_raw_fd())
+        }
+    }
+}
+
+#[cfg(feature = "io_safety")]
+/// Requires the `io_safety` feature.
+impl From<UnixSeqpacketListener> for std::os::unix::io::OwnedFd {
+    fn from(socket: UnixSeqpacketListener) -> std::os::unix::io::OwnedFd {
+        use std::os::unix::io::FromRawFd;
+
+        unsafe { std::os::unix::io::OwnedFd::from_raw_fd(socket.inner.into_raw_fd()) }
+    }
+}
+
+impl<'a> IntoIterator for &'a UnixSeqpacketListener {
+    type Item = io::Result<UnixSeqpacket>;
+    type IntoIter = SeqpacketIncoming<'a>;
+
+    fn into_iter(self) -> SeqpacketIncoming<'a> {
+        self.incoming()
+    }
+}
+
+/// An iterator over incoming connections to a `UnixSeqpacketListener`.
+///
+/// It will never return `None`.
+#[derive(Debug)]
+pub struct SeqpacketIncoming<'a> {
+    listener: &'a UnixSeqpacketListener,
+}
+
+impl<'a> Iterator for SeqpacketIncoming<'a> {
+    type Item = io::Result<UnixSeqpacket>;
+
+    fn next(&mut self) -> Option<io::Result<UnixSeqpacket>> {
+        Some(self.listener.accept())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::max_value(), None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use std::thread;
+    use std::fs::File;
+    use std::io;
     use std::io::prelude::*;
+    use std::mem;
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::path::Path;
+    use std::process::Command;
+    use std::ptr;
+    use std::time::Duration;
     use self::tempdir::TempDir;
+    use libc;
+
+    use {UnixListener, ListenerOptions, UnixListenerGuard, AuthenticatedListener, UnixStream,
+         UnixSocketError, ConnectOptions, ConnectState, BufUnixStream, FramedUnixStream,
+         UnixDatagram, DatagramOptions, DatagramMsg, SendMsg, UnixSeqpacket, RecvMsg,
+         UnixSeqpacketListener, AbstractPath, AbstractPathBuf, AddressKind, AsciiEscaped,
+         AncillaryData, AncillaryMessage, CmsgEntry, CmsgIter, cmsg_space_for_fds, SocketAddr,
+         ReuniteError, UCred, Inner, cvt, SUN_PATH_MAX, sockaddr_un};
+
+    macro_rules! or_panic {
+        ($e:expr) => {
+            match $e {
+                Ok(e) => e,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    // UnixStream has no way to bind before connecting, so do it by hand
+    fn bound_client<P: AsRef<Path>, Q: AsRef<Path>>(client_path: P, socket_path: Q) -> Inner {
+        unsafe {
+            let client = Inner(or_panic!(cvt(libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0))));
+            let (addr, len) = or_panic!(sockaddr_un(client_path));
+            or_panic!(cvt(libc::bind(client.0, &addr as *const _ as *const _, len)));
+            let (addr, len) = or_panic!(sockaddr_un(socket_path));
+            or_panic!(cvt(libc::connect(client.0, &addr as *const _ as *const _, len)));
+            client
+        }
+    }
+
+    // Creates a file in `dir`, along with ancillary data passing its descriptor
+    fn fd_fixture(dir: &TempDir) -> (File, AncillaryData) {
+        let file = or_panic!(File::create(dir.path().join("file")));
+        let mut ancillary = AncillaryData::new();
+        or_panic!(ancillary.add_fds(&[file.as_raw_fd()]));
+        (file, ancillary)
+    }
+
+    // Checks that `received` holds a single descriptor which refers to `file`,
+    // and closes it
+    fn check_fd_fixture(file: &File, received: &AncillaryMessage) {
+        let fd = match received.entries().collect::<Vec<_>>().as_slice() {
+            [CmsgEntry::Rights(fds)] if fds.len() == 1 => fds[0],
+            entries => panic!("unexpected entries {:?}", entries),
+        };
+        let len = or_panic!(file.metadata()).len();
+        or_panic!(unsafe { File::from_raw_fd(fd) }.write_all(b"abc"));
+        assert_eq!(or_panic!(file.metadata()).len(), len + 3);
+    }
+
+    #[test]
+    fn basic() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let msg1 = b"hello";
+        let msg2 = b"world!";
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let thread = thread::spawn(move || {
+            let mut stream = or_panic!(listener.accept());
+            let mut buf = [0; 5];
+            or_panic!(stream.read(&mut buf));
+            assert_eq!(&msg1[..], &buf[..]);
+            or_panic!(stream.write_all(msg2));
+        });
+
+        let mut stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(stream.write_all(msg1));
+        let mut buf = vec![];
+        or_panic!(stream.read_to_end(&mut buf));
+        assert_eq!(&msg2[..], &buf[..]);
+        drop(stream);
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn unnamed() {
+        let msg1 = b"hello";
+        let msg2 = b"world!";
+
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            // s1 must be moved in or the test will hang!
+            let mut buf = [0; 5];
+            or_panic!(s1.read(&mut buf));
+            assert_eq!(&msg1[..], &buf[..]);
+            or_panic!(s1.write_all(msg2));
+        });
+
+        or_panic!(s2.write_all(msg1));
+        let mut buf = vec![];
+        or_panic!(s2.read_to_end(&mut buf));
+        assert_eq!(&msg2[..], &buf[..]);
+        drop(s2);
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn abstract_address() {
+        let socket_path = "\0the path";
+        let msg1 = b"hello";
+        let msg2 = b"world!";
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let thread = thread::spawn(move || {
+            let mut stream = or_panic!(listener.accept());
+            let mut buf = [0; 5];
+            or_panic!(stream.read(&mut buf));
+            assert_eq!(&msg1[..], &buf[..]);
+            or_panic!(stream.write_all(msg2));
+        });
+
+        let mut stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(stream.write_all(msg1));
+        let mut buf = vec![];
+        or_panic!(stream.read_to_end(&mut buf));
+        assert_eq!(&msg2[..], &buf[..]);
+        drop(stream);
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn try_clone() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let msg1 = b"hello";
+        let msg2 = b"world";
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let thread = thread::spawn(move || {
+            let mut stream = or_panic!(listener.accept());
+            or_panic!(stream.write_all(msg1));
+            or_panic!(stream.write_all(msg2));
+        });
+
+        let mut stream = or_panic!(UnixStream::connect(&socket_path));
+        let mut stream2 = or_panic!(stream.try_clone());
+
+        let mut buf = [0; 5];
+        or_panic!(stream.read(&mut buf));
+        assert_eq!(&msg1[..], &buf[..]);
+        or_panic!(stream2.read(&mut buf));
+        assert_eq!(&msg2[..], &buf[..]);
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn iter() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = or_panic!(stream);
+                let mut buf = [0];
+                or_panic!(stream.read(&mut buf));
+            }
+        });
+
+        for _ in 0..2 {
+            let mut stream = or_panic!(UnixStream::connect(&socket_path));
+            or_panic!(stream.write_all(&[0]));
+        }
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn into_incoming() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let incoming = or_panic!(UnixListener::bind(&socket_path)).into_incoming();
+        let addr = or_panic!(incoming.listener().local_addr());
+        let thread = thread::spawn(move || {
+            for stream in incoming.take(2) {
+                let mut stream = or_panic!(stream);
+                let mut buf = [0];
+                or_panic!(stream.read_exact(&mut buf));
+                or_panic!(stream.write_all(&buf));
+            }
+        });
+
+        for i in 0..2 {
+            let mut stream = or_panic!(UnixStream::connect(&socket_path));
+            or_panic!(stream.write_all(&[i]));
+            let mut buf = [0];
+            or_panic!(stream.read_exact(&mut buf));
+            assert_eq!(i, buf[0]);
+        }
+
+        thread.join().unwrap();
+        assert_eq!(Some(&*socket_path), addr.address().as_pathname());
+    }
+
+    #[test]
+    fn incoming_fallible() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let _s1 = or_panic!(UnixStream::connect(&socket_path));
+        let _s2 = or_panic!(UnixStream::connect(&socket_path));
+        let _s3 = or_panic!(UnixStream::connect(&socket_path));
+        {
+            let mut incoming = listener.incoming_fallible();
+            assert!(incoming.next().is_some());
+            assert!(incoming.last_error().is_none());
+        }
+
+        // Exhausting file descriptors affects the whole process, so do it in a
+        // child to keep it from interfering with other tests.
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                let limit = libc::rlimit { rlim_cur: 256, rlim_max: 256 };
+                libc::setrlimit(libc::RLIMIT_NOFILE, &limit);
+                while libc::dup(listener.as_raw_fd()) >= 0 {}
+
+                let mut incoming = listener.incoming().stop_on_error();
+                let ok = incoming.next().is_none() && incoming.next().is_none() &&
+                         incoming.last_error().and_then(|e| e.raw_os_error()) ==
+                         Some(libc::EMFILE);
+                libc::_exit(if ok { 0 } else { 1 });
+            }
+
+            let mut status = 0;
+            assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+            assert!(libc::WIFEXITED(status));
+            assert_eq!(0, libc::WEXITSTATUS(status));
+        }
+
+        // the child's failed accepts left the queued connection in place
+        assert!(listener.incoming_fallible().next().is_some());
+    }
+
+    #[test]
+    fn accept_addr() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let client_path = dir.path().join("client");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+
+        let _client = bound_client(&client_path, &socket_path);
+        let (_stream, addr) = or_panic!(listener.accept_addr());
+        match addr.address() {
+            AddressKind::Pathname(path) => assert_eq!(client_path, path),
+            addr => panic!("unexpected address {:?}", addr),
+        }
+
+        let _client = or_panic!(UnixStream::connect(&socket_path));
+        let (_stream, addr) = or_panic!(listener.accept_addr());
+        match addr.address() {
+            AddressKind::Unnamed => {}
+            addr => panic!("unexpected address {:?}", addr),
+        }
+    }
+
+    #[test]
+    fn backlog() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind_with_backlog(&socket_path, 1));
+        or_panic!(listener.set_backlog(4096));
+        or_panic!(listener.set_backlog(1));
+
+        let _stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(listener.accept());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn listener_options() {
+        use std::time::Duration;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(ListenerOptions::new()
+                                     .backlog(2)
+                                     .nonblocking(true)
+                                     .cloexec(true)
+                                     .reuse_addr(true)
+                                     .bind(&socket_path));
+        assert!(is_cloexec(&listener));
+        match listener.accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Linux allows one more pending connection than the backlog
+        let mut streams = vec![];
+        loop {
+            match UnixStream::connect_timeout(&socket_path, Duration::from_millis(50)) {
+                Ok(stream) => streams.push(stream),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => panic!("unexpected error {}", e),
+            }
+            assert!(streams.len() <= 3, "backlog never filled");
+        }
+        assert_eq!(3, streams.len());
+        or_panic!(listener.accept());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn connect_options() {
+        use std::time::{Duration, Instant};
+
+        fn is_nonblocking<T: AsRawFd>(socket: &T) -> bool {
+            let flags = unsafe { libc::fcntl(socket.as_raw_fd(), libc::F_GETFL) };
+            assert!(flags >= 0);
+            flags & libc::O_NONBLOCK != 0
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        match ConnectOptions::new().timeout(Duration::from_millis(100)).connect(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let _listener = or_panic!(UnixListener::bind_with_backlog(&socket_path, 1));
+        let stream = or_panic!(ConnectOptions::new()
+                                   .timeout(Duration::from_millis(100))
+                                   .nonblocking(true)
+                                   .cloexec(true)
+                                   .connect(&socket_path));
+        assert!(is_nonblocking(&stream));
+        assert!(is_cloexec(&stream));
+
+        let stream = or_panic!(ConnectOptions::new().connect(&socket_path));
+        assert!(!is_nonblocking(&stream));
+        assert!(!is_cloexec(&stream));
+
+        // the backlog is now full
+        let start = Instant::now();
+        match ConnectOptions::new().timeout(Duration::from_millis(100)).connect(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        match ConnectOptions::new().nonblocking(true).connect(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connect_nonblocking() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind_with_backlog(&socket_path, 1));
+        let pending = or_panic!(UnixStream::connect_nonblocking(&socket_path));
+        let mut stream = match or_panic!(pending.poll(None)) {
+            ConnectState::Connected(stream) => stream,
+            ConnectState::InProgress(pending) => {
+                or_panic!(pending.wait(Some(Duration::from_secs(1))))
+            }
+            state => panic!("unexpected state {:?}", state),
+        };
+        let mut accepted = or_panic!(listener.accept());
+        or_panic!(stream.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(accepted.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn connect_nonblocking_backlog() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind_with_backlog(&socket_path, 0));
+        let _s1 = or_panic!(UnixStream::connect(&socket_path));
+
+        // the backlog is now full, so the connection can't complete
+        let pending = or_panic!(UnixStream::connect_nonblocking(&socket_path));
+        let pending = match or_panic!(pending.poll(None)) {
+            ConnectState::InProgress(pending) => pending,
+            state => panic!("unexpected state {:?}", state),
+        };
+        let pending = match or_panic!(pending.poll(Some(Duration::from_millis(50)))) {
+            ConnectState::TimedOut(pending) => pending,
+            state => panic!("unexpected state {:?}", state),
+        };
+        assert!(pending.as_raw_fd() >= 0);
+
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            or_panic!(listener.accept());
+            listener
+        });
+        let stream = or_panic!(pending.wait(Some(Duration::from_secs(5))));
+        let listener = thread.join().unwrap();
+        or_panic!(listener.accept());
+        assert_eq!(or_panic!(listener.local_addr()), or_panic!(stream.peer_addr()));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn connect_timeout() {
+        use std::time::{Duration, Instant};
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let _listener = or_panic!(UnixListener::bind_with_backlog(&socket_path, 1));
+
+        let mut streams = vec![];
+        for _ in 0..10 {
+            let start = Instant::now();
+            match UnixStream::connect_timeout(&socket_path, Duration::from_millis(200)) {
+                Ok(stream) => streams.push(stream),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    let wait = start.elapsed();
+                    assert!(wait > Duration::from_millis(100));
+                    assert!(wait < Duration::from_millis(1000));
+                    return;
+                }
+                Err(e) => panic!("unexpected error {}", e),
+            }
+        }
+        panic!("backlog never filled");
+    }
+
+    #[test]
+    fn iter_with_addr() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let client_path1 = dir.path().join("client1");
+        let client_path2 = dir.path().join("client2");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let _client1 = bound_client(&client_path1, &socket_path);
+        let _client2 = bound_client(&client_path2, &socket_path);
+
+        let addrs = listener.incoming_with_addr()
+                            .take(2)
+                            .map(|r| or_panic!(r).1)
+                            .collect::<Vec<_>>();
+        match (addrs[0].address(), addrs[1].address()) {
+            (AddressKind::Pathname(path1), AddressKind::Pathname(path2)) => {
+                assert_eq!(client_path1, path1);
+                assert_eq!(client_path2, path2);
+            }
+            addrs => panic!("unexpected addresses {:?}", addrs),
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn addr_hash_eq() {
+        use std::collections::HashMap;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let abstract_listener = or_panic!(UnixListener::bind("\0the hashed path"));
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+
+        let mut map = HashMap::new();
+        map.insert(or_panic!(listener.local_addr()), "pathname");
+        map.insert(or_panic!(abstract_listener.local_addr()), "abstract");
+        map.insert(or_panic!(s1.local_addr()), "unnamed");
+        assert_eq!(3, map.len());
+
+        assert_eq!(Some(&"pathname"), map.get(&or_panic!(listener.local_addr())));
+        assert_eq!(Some(&"abstract"), map.get(&or_panic!(abstract_listener.local_addr())));
+        assert_eq!(Some(&"unnamed"), map.get(&or_panic!(s2.local_addr())));
+
+        let stream = or_panic!(UnixStream::connect(&socket_path));
+        assert_eq!(or_panic!(listener.local_addr()), or_panic!(stream.peer_addr()));
+        assert!(or_panic!(listener.local_addr()) != or_panic!(stream.local_addr()));
+    }
+
+    #[test]
+    fn nonblocking() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        assert!(!or_panic!(listener.nonblocking()));
+        or_panic!(listener.set_nonblocking(true));
+        assert!(or_panic!(listener.nonblocking()));
+        match listener.accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+
+        let mut stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(stream.set_nonblocking(true));
+        assert!(or_panic!(stream.nonblocking()));
+        let mut buf = [0; 10];
+        match stream.read(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+        or_panic!(stream.set_nonblocking(false));
+        assert!(!or_panic!(stream.nonblocking()));
+
+        let socket = or_panic!(UnixDatagram::unbound());
+        or_panic!(socket.set_nonblocking(true));
+        assert!(or_panic!(socket.nonblocking()));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn take_error() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        assert!(or_panic!(s1.take_error()).is_none());
+
+        // closing a socket with unread data resets the connection
+        or_panic!(s1.write_all(b"hello"));
+        drop(s2);
+
+        match or_panic!(s1.take_error()) {
+            Some(ref e) if e.kind() == io::ErrorKind::ConnectionReset => {}
+            Some(e) => panic!("unexpected error {}", e),
+            None => panic!("expected an error"),
+        }
+        assert!(or_panic!(s1.take_error()).is_none());
+
+        // a nonblocking connect to a missing path fails either immediately or,
+        // if it completes asynchronously, with the error left in SO_ERROR
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let stream = UnixStream { inner: or_panic!(Inner::new(libc::SOCK_STREAM)) };
+        or_panic!(stream.set_nonblocking(true));
+        let (addr, len) = or_panic!(unsafe { sockaddr_un(dir.path().join("missing")) });
+        let res = cvt(unsafe {
+            libc::connect(stream.inner.0, &addr as *const _ as *const _, len)
+        });
+        let err = match res {
+            Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {
+                or_panic!(stream.inner.poll(libc::POLLOUT, Duration::from_secs(10)));
+                or_panic!(stream.take_error()).expect("expected an error")
+            }
+            Err(e) => {
+                assert!(or_panic!(stream.take_error()).is_none());
+                e
+            }
+            Ok(_) => panic!("connected to a missing path"),
+        };
+        match err.raw_os_error() {
+            Some(libc::ENOENT) | Some(libc::ECONNREFUSED) => {}
+            _ => panic!("unexpected error {}", err),
+        }
+
+        let listener = or_panic!(UnixListener::bind("\0the error path"));
+        assert!(or_panic!(listener.take_error()).is_none());
+        let socket = or_panic!(UnixDatagram::unbound());
+        assert!(or_panic!(socket.take_error()).is_none());
+    }
+
+    #[test]
+    #[cfg(any(has_so_peercred, has_local_peercred, has_getpeereid))]
+    fn peer_cred() {
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+
+        let cred = or_panic!(s1.peer_cred());
+        assert_eq!(unsafe { libc::getuid() }, cred.uid);
+        assert_eq!(unsafe { libc::getgid() }, cred.gid);
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(unsafe { libc::getpid() }, cred.pid);
+        } else {
+            assert_eq!(-1, cred.pid);
+        }
+        assert_eq!(cred, or_panic!(s2.peer_cred()));
+    }
+
+    #[test]
+    #[cfg(not(any(has_so_peercred, has_local_peercred, has_getpeereid)))]
+    fn peer_cred_unsupported() {
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(s1.peer_cred().unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn authenticated_listener() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let uid = unsafe { libc::getuid() };
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let mut listener = AuthenticatedListener::new(listener, move |cred: &UCred| {
+            cred.uid == uid
+        });
+        let mut s1 = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(s1.write_all(b"a"));
+        let mut stream = or_panic!(listener.next().unwrap());
+        let mut buf = [0];
+        or_panic!(stream.read_exact(&mut buf));
+        assert_eq!(b"a", &buf);
+
+        // a peer running as another user would be rejected
+        let other = UCred { uid: uid.wrapping_add(1), gid: 0, pid: -1 };
+        assert!(!(listener.policy)(&other));
+
+        let listener = AuthenticatedListener::new(listener.into_inner(), |cred: &UCred| {
+            cred.uid != uid
+        });
+        let mut s2 = or_panic!(UnixStream::connect(&socket_path));
+        let _s3 = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(listener.listener().set_nonblocking(true));
+        match listener.accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(0, or_panic!(s2.read(&mut buf)));
+    }
+
+    #[test]
+    fn check_peer() {
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+
+        or_panic!(s1.check_peer_uid(uid));
+        or_panic!(s1.check_peer_gid(gid));
+        or_panic!(s1.check_peer_uid_in(&[uid.wrapping_add(1), uid]));
+        or_panic!(s1.check_peer_gid_in(&[gid.wrapping_add(1), gid]));
+
+        let results = [s1.check_peer_uid(uid.wrapping_add(1)),
+                       s1.check_peer_gid(gid.wrapping_add(1)),
+                       s1.check_peer_uid_in(&[]),
+                       s1.check_peer_gid_in(&[gid.wrapping_add(1)])];
+        for result in &results {
+            match *result {
+                Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+                ref other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn pass_fds() {
+        use std::fs::File;
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let file_path = dir.path().join("file");
+        {
+            let mut file = or_panic!(File::create(&file_path));
+            or_panic!(file.write_all(b"file contents"));
+        }
+        let file = or_panic!(File::open(&file_path));
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(5, or_panic!(s1.send_fds(b"hello", &[file.as_raw_fd()])));
+        drop(file);
+
+        let mut buf = [0; 10];
+        let (count, fds) = or_panic!(s2.recv_fds(&mut buf, 1));
+        assert_eq!(b"hello", &buf[..count]);
+        assert_eq!(1, fds.len());
+
+        let mut file = unsafe { File::from_raw_fd(fds[0]) };
+        let mut contents = String::new();
+        or_panic!(file.read_to_string(&mut contents));
+        assert_eq!("file contents", contents);
+
+        assert_eq!(5, or_panic!(s1.send_fds(b"world", &[])));
+        let (count, fds) = or_panic!(s2.recv_fds(&mut buf, 1));
+        assert_eq!(b"world", &buf[..count]);
+        assert!(fds.is_empty());
+    }
+
+    #[test]
+    fn ancillary() {
+        use std::fs::File;
+        use std::os::unix::io::AsRawFd;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let file1 = or_panic!(File::create(dir.path().join("file1")));
+        let file2 = or_panic!(File::create(dir.path().join("file2")));
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+
+        let mut data = AncillaryData::new();
+        assert!(data.is_empty());
+        or_panic!(data.add_fds(&[file1.as_raw_fd()]));
+        or_panic!(data.add_fds(&[file1.as_raw_fd(), file2.as_raw_fd()]));
+        assert!(!data.is_empty());
+        assert_eq!(5, or_panic!(s1.send_with_ancillary(b"hello", &data)));
+
+        let mut msg = AncillaryMessage::with_capacity(1024);
+        let mut buf = [0; 10];
+        assert_eq!(5, or_panic!(s2.recv_with_ancillary(&mut buf, &mut msg)));
+        assert!(!msg.is_truncated());
+
+        let mut count = 0;
+        for entry in msg.entries() {
+            match entry {
+                CmsgEntry::Rights(fds) => {
+                    for fd in fds {
+                        count += 1;
+                        drop(Inner(fd));
+                    }
+                }
+                entry => panic!("unexpected entry {:?}", entry),
+            }
+        }
+        assert_eq!(3, count);
+
+        data.clear();
+        assert_eq!(5, or_panic!(s1.send_with_ancillary(b"world", &data)));
+        assert_eq!(5, or_panic!(s2.recv_with_ancillary(&mut buf, &mut msg)));
+        assert_eq!(0, msg.entries().count());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn seqpacket() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let msg1 = [1; 512];
+        let msg2 = [2; 512];
+
+        let listener = or_panic!(UnixSeqpacketListener::bind(&socket_path));
+        let thread = thread::spawn(move || {
+            let socket = or_panic!(listener.accept());
+            or_panic!(socket.send(&msg1));
+            or_panic!(socket.send(&msg2));
+        });
+
+        let socket = or_panic!(UnixSeqpacket::connect(&socket_path));
+        let mut buf = [0; 1024];
+        assert_eq!(512, or_panic!(socket.recv(&mut buf)));
+        assert_eq!(&msg1[..], &buf[..512]);
+        assert_eq!(512, or_panic!(socket.recv(&mut buf)));
+        assert_eq!(&msg2[..], &buf[..512]);
+
+        thread.join().unwrap();
+        assert_eq!(0, or_panic!(socket.recv(&mut buf)));
+    }
+
+    #[test]
+    fn datagram_pair() {
+        let msg1 = b"hello";
+        let msg2 = b"world!";
+
+        let (s1, s2) = or_panic!(UnixDatagram::pair());
+        or_panic!(s1.send(msg1));
+        or_panic!(s1.send(msg2));
+
+        let mut buf = [0; 10];
+        assert_eq!(5, or_panic!(s2.recv(&mut buf)));
+        assert_eq!(&msg1[..], &buf[..5]);
+        assert_eq!(6, or_panic!(s2.recv(&mut buf)));
+        assert_eq!(&msg2[..], &buf[..6]);
+
+        or_panic!(s2.send(msg1));
+        assert_eq!(5, or_panic!(s1.recv(&mut buf)));
+        assert_eq!(&msg1[..], &buf[..5]);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn seqpacket_pair() {
+        let msg1 = b"hello";
+        let msg2 = b"world!";
+
+        let (s1, s2) = or_panic!(UnixSeqpacket::pair());
+        or_panic!(s1.send(msg1));
+        or_panic!(s1.send(msg2));
+
+        let mut buf = [0; 10];
+        assert_eq!(5, or_panic!(s2.recv(&mut buf)));
+        assert_eq!(&msg1[..], &buf[..5]);
+        assert_eq!(6, or_panic!(s2.recv(&mut buf)));
+        assert_eq!(&msg2[..], &buf[..6]);
+
+        or_panic!(s2.send(msg1));
+        assert_eq!(5, or_panic!(s1.recv(&mut buf)));
+        assert_eq!(&msg1[..], &buf[..5]);
+    }
+
+    #[test]
+    #[cfg(feature = "io_safety")]
+    fn io_safety() {
+        use std::os::unix::io::{AsFd, AsRawFd, OwnedFd};
+
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        let fd = s2.as_raw_fd();
+        assert_eq!(fd, s2.as_fd().as_raw_fd());
+
+        let owned = OwnedFd::from(s2);
+        assert_eq!(fd, owned.as_raw_fd());
+        let mut s2 = UnixStream::from(owned);
+
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    fn addr_constructors() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let addr = or_panic!(SocketAddr::from_path(&socket_path));
+        match addr.address() {
+            AddressKind::Pathname(path) => assert_eq!(socket_path, path),
+            addr => panic!("unexpected address {:?}", addr),
+        }
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        assert_eq!(or_panic!(listener.local_addr()), addr);
+
+        let addr = or_panic!(SocketAddr::from_abstract(b"the name"));
+        match addr.address() {
+            AddressKind::Abstract(name) => assert_eq!(b"the name", name),
+            addr => panic!("unexpected address {:?}", addr),
+        }
+        assert_eq!(or_panic!(SocketAddr::from_path("\0the name")), addr);
+
+        match SocketAddr::unnamed().address() {
+            AddressKind::Unnamed => {}
+            addr => panic!("unexpected address {:?}", addr),
+        }
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(or_panic!(s1.local_addr()), SocketAddr::unnamed());
+    }
+
+    #[test]
+    fn addr_display_from_str() {
+        let addr = or_panic!(SocketAddr::from_path("/tmp/sock"));
+        assert_eq!("/tmp/sock", addr.to_string());
+        assert_eq!(addr, or_panic!("/tmp/sock".parse::<SocketAddr>()));
+
+        let addr = or_panic!(SocketAddr::from_abstract(b"name\0with\n\xffbytes\\"));
+        assert_eq!("@name\\x00with\\n\\xffbytes\\\\", addr.to_string());
+        assert_eq!(addr, or_panic!(addr.to_string().parse::<SocketAddr>()));
+
+        let addr = or_panic!("@name".parse::<SocketAddr>());
+        match addr.address() {
+            AddressKind::Abstract(name) => assert_eq!(b"name", name),
+            addr => panic!("unexpected address {:?}", addr),
+        }
+
+        let addr = SocketAddr::unnamed();
+        assert_eq!("(unnamed)", addr.to_string());
+        assert_eq!(addr, or_panic!("(unnamed)".parse::<SocketAddr>()));
+
+        assert!("".parse::<SocketAddr>().is_err());
+        assert!("@bad\\q".parse::<SocketAddr>().is_err());
+    }
+
+    #[test]
+    fn address_kind() {
+        use std::collections::HashSet;
+
+        let path = Path::new("/tmp/sock");
+        let kinds = [AddressKind::Unnamed, AddressKind::Pathname(path), AddressKind::Abstract(b"name")];
+
+        let mut set = HashSet::new();
+        for kind in &kinds {
+            set.insert(*kind);
+            set.insert(*kind);
+        }
+        assert_eq!(3, set.len());
+        assert!(set.contains(&AddressKind::Pathname(Path::new("/tmp/sock"))));
+        assert!(set.contains(&AddressKind::Abstract(b"name")));
+        assert!(AddressKind::Abstract(b"name") != AddressKind::Abstract(b"other"));
+        assert!(AddressKind::Unnamed != AddressKind::Abstract(b""));
+
+        assert!(kinds[0].is_unnamed());
+        assert_eq!(Some(path), kinds[1].as_pathname());
+        assert_eq!(Some(&b"name"[..]), kinds[2].as_abstract());
+        assert_eq!(None, kinds[0].as_abstract());
+
+        assert_eq!("(unnamed)", kinds[0].to_string());
+        assert_eq!("/tmp/sock", kinds[1].to_string());
+        assert_eq!("@name", kinds[2].to_string());
+    }
+
+    #[test]
+    fn listener_guard() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListenerGuard::bind(&socket_path));
+        assert_eq!(&*socket_path, listener.path());
+        let _stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(listener.accept());
+        assert!(socket_path.exists());
+        drop(listener);
+        assert!(!socket_path.exists());
+
+        let listener = or_panic!(UnixListenerGuard::bind(&socket_path));
+        let (listener, path) = listener.into_inner();
+        assert_eq!(socket_path, path);
+        drop(listener);
+        assert!(socket_path.exists());
+    }
+
+    #[test]
+    fn bind_replace() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let old_listener = or_panic!(UnixListener::bind(&socket_path));
+        let listener = or_panic!(UnixListener::bind_replace(&socket_path));
+
+        let mut stream = or_panic!(UnixStream::connect(&socket_path));
+        or_panic!(stream.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(or_panic!(listener.accept()).read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        or_panic!(old_listener.set_nonblocking(true));
+        match old_listener.accept() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+
+        let entries = or_panic!(::std::fs::read_dir(dir.path())).count();
+        assert_eq!(1, entries);
+
+        match UnixListener::bind_replace("\0the replaced path") {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    #[test]
+    fn peek() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(s1.write_all(b"hello"));
+
+        let mut buf = [0; 5];
+        assert_eq!(5, or_panic!(s2.peek(&mut buf)));
+        assert_eq!(b"hello", &buf);
+        buf = [0; 5];
+        assert_eq!(5, or_panic!(s2.peek(&mut buf)));
+        assert_eq!(b"hello", &buf);
+        buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        or_panic!(s2.set_nonblocking(true));
+        match s2.peek(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let sender_path = dir.path().join("sender");
+        let receiver = or_panic!(UnixDatagram::bind(&path));
+        let sender = or_panic!(UnixDatagram::bind(&sender_path));
+        or_panic!(sender.send_to(b"hello", &path));
+
+        let (len, addr) = or_panic!(receiver.peek_from(&mut buf));
+        assert_eq!(5, len);
+        assert_eq!(Some(&*sender_path), addr.address().as_pathname());
+        buf = [0; 5];
+        let (len, addr) = or_panic!(receiver.recv_from(&mut buf));
+        assert_eq!(5, len);
+        assert_eq!(b"hello", &buf);
+        assert_eq!(Some(&*sender_path), addr.address().as_pathname());
+    }
+
+    #[test]
+    fn dontwait() {
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        let mut buf = [0; 5];
+        match s2.recv_nonblocking(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(!or_panic!(s2.nonblocking()));
+        assert_eq!(5, or_panic!(s1.send_nonblocking(b"hello")));
+        assert_eq!(5, or_panic!(s2.recv_nonblocking(&mut buf)));
+        assert_eq!(b"hello", &buf);
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        match d2.recv_nonblocking(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(!or_panic!(d2.nonblocking()));
+        assert_eq!(5, or_panic!(d1.send_nonblocking(b"hello")));
+        assert_eq!(5, or_panic!(d2.recv_nonblocking(&mut buf)));
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    fn with_flags() {
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            for chunk in [&b"he"[..], b"ll", b"o"].iter() {
+                or_panic!(s1.send_with_flags(chunk, 0));
+                thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+
+        let mut buf = [0; 5];
+        assert_eq!(5, or_panic!(s2.recv_with_flags(&mut buf, libc::MSG_WAITALL)));
+        assert_eq!(b"hello", &buf);
+        thread.join().unwrap();
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let receiver = or_panic!(UnixDatagram::bind(&path));
+        let sender = or_panic!(UnixDatagram::unbound());
+        or_panic!(sender.send_to_with_flags(b"hello", &path, 0));
+        buf = [0; 5];
+        let (len, _) = or_panic!(receiver.recv_from_with_flags(&mut buf, libc::MSG_PEEK));
+        assert_eq!(5, len);
+        let (len, _) = or_panic!(receiver.recv_from_with_flags(&mut buf, 0));
+        assert_eq!(5, len);
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    fn buffer_sizes() {
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(s1.set_send_buffer_size(64 * 1024));
+        assert!(or_panic!(s1.send_buffer_size()) >= 64 * 1024);
+        or_panic!(s1.set_recv_buffer_size(64 * 1024));
+        assert!(or_panic!(s1.recv_buffer_size()) >= 64 * 1024);
+
+        let (d1, _d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d1.set_send_buffer_size(64 * 1024));
+        assert!(or_panic!(d1.send_buffer_size()) >= 64 * 1024);
+        or_panic!(d1.set_recv_buffer_size(64 * 1024));
+        assert!(or_panic!(d1.recv_buffer_size()) >= 64 * 1024);
+    }
+
+    fn is_cloexec<T: AsRawFd>(socket: &T) -> bool {
+        let flags = unsafe { libc::fcntl(socket.as_raw_fd(), libc::F_GETFD) };
+        assert!(flags >= 0);
+        flags & libc::FD_CLOEXEC != 0
+    }
+
+    #[test]
+    fn cloexec() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let dgram_path = dir.path().join("dgram");
+
+        let listener = or_panic!(UnixListener::bind_cloexec(&socket_path));
+        let stream = or_panic!(UnixStream::connect_cloexec(&socket_path));
+        let plain = or_panic!(UnixStream::connect(&socket_path));
+        let datagram = or_panic!(UnixDatagram::bind_cloexec(&dgram_path));
+        assert!(is_cloexec(&listener));
+        assert!(is_cloexec(&stream));
+        assert!(is_cloexec(&datagram));
+        assert!(!is_cloexec(&plain));
+
+        let clone = or_panic!(plain.try_clone());
+        let clone_cloexec = or_panic!(plain.try_clone_cloexec());
+        assert!(!is_cloexec(&clone));
+        assert!(is_cloexec(&clone_cloexec));
+
+        if Path::new("/proc/self/fd").exists() {
+            let inherited = |fd: RawFd| {
+                or_panic!(Command::new("sh")
+                              .arg("-c")
+                              .arg(format!("test -e /proc/self/fd/{}", fd))
+                              .status())
+                    .success()
+            };
+            assert!(!inherited(stream.as_raw_fd()));
+            assert!(inherited(plain.as_raw_fd()));
+        }
+    }
+
+    #[test]
+    fn broken_pipe() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        drop(s2);
+
+        // The test harness ignores SIGPIPE, so restore its default action in
+        // a child, where a raised signal would kill the process.
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+                let bufs = [io::IoSlice::new(b"hello")];
+                let ok = [s1.write(b"hello"), s1.write_vectored(&bufs)].iter().all(|res| {
+                    match *res {
+                        Err(ref e) => e.kind() == io::ErrorKind::BrokenPipe,
+                        Ok(_) => false,
+                    }
+                });
+                libc::_exit(if ok { 0 } else { 1 });
+            }
+
+            let mut status = 0;
+            assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+            assert!(libc::WIFEXITED(status), "child killed by signal {}", libc::WTERMSIG(status));
+            assert_eq!(0, libc::WEXITSTATUS(status));
+        }
+    }
+
+    #[test]
+    fn vectored() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        let bufs = [io::IoSlice::new(b"hel"), io::IoSlice::new(b"lo")];
+        assert_eq!(5, or_panic!(s1.write_vectored(&bufs)));
+
+        let mut head = [0; 2];
+        let mut tail = [0; 3];
+        {
+            let mut bufs = [io::IoSliceMut::new(&mut head), io::IoSliceMut::new(&mut tail)];
+            assert_eq!(5, or_panic!(s2.read_vectored(&mut bufs)));
+        }
+        assert_eq!(b"he", &head);
+        assert_eq!(b"llo", &tail);
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        assert_eq!(5, or_panic!(d1.send_vectored(&bufs)));
+        let mut buf = [0; 5];
+        assert_eq!(5, or_panic!(d2.recv(&mut buf)));
+        assert_eq!(b"hello", &buf);
+
+        assert_eq!(5, or_panic!(d1.send(b"hello")));
+        let mut bufs = [io::IoSliceMut::new(&mut head), io::IoSliceMut::new(&mut tail)];
+        assert_eq!(5, or_panic!(d2.recv_vectored(&mut bufs)));
+        assert_eq!(b"he", &head);
+        assert_eq!(b"llo", &tail);
+    }
 
-    use {UnixListener, UnixStream, UnixDatagram};
+    #[test]
+    fn split() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        {
+            let (mut read, mut write) = s1.split();
+            or_panic!(write.write_all(b"hello"));
+            let mut buf = [0; 5];
+            or_panic!(s2.read_exact(&mut buf));
+            assert_eq!(b"hello", &buf);
+            or_panic!(s2.write_all(b"world"));
+            or_panic!(read.read_exact(&mut buf));
+            assert_eq!(b"world", &buf);
+        }
 
-    macro_rules! or_panic {
-        ($e:expr) => {
-            match $e {
-                Ok(e) => e,
-                Err(e) => panic!("{}", e),
+        let (mut read, mut write) = s1.into_split();
+        let writer = thread::spawn(move || {
+            for _ in 0..100 {
+                or_panic!(write.write_all(b"ping"));
+            }
+            write
+        });
+        let reader = thread::spawn(move || {
+            let mut buf = [0; 4];
+            for _ in 0..100 {
+                or_panic!(read.read_exact(&mut buf));
+                assert_eq!(b"pong", &buf);
+            }
+            read
+        });
+        let mut buf = [0; 4];
+        for _ in 0..100 {
+            or_panic!(s2.read_exact(&mut buf));
+            assert_eq!(b"ping", &buf);
+            or_panic!(s2.write_all(b"pong"));
+        }
+        let write = writer.join().unwrap();
+        let read = reader.join().unwrap();
+
+        let (other_read, other_write) = or_panic!(UnixStream::unnamed()).0.into_split();
+        let read = match read.reunite(other_write) {
+            Err(ReuniteError(read, _)) => read,
+            Ok(_) => panic!("reunited halves of different streams"),
+        };
+        drop(other_read);
+
+        let mut s1 = or_panic!(read.reunite(write));
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    fn send_recv_many() {
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        let messages = (0..100u8)
+            .map(|i| {
+                DatagramMsg {
+                    data: vec![i; i as usize + 1],
+                    addr: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(100, or_panic!(d1.send_many(&messages)));
+
+        let mut received = vec![];
+        let mut buf = vec![0; 100 * 128];
+        assert_eq!(100, or_panic!(d2.recv_many(&mut received, &mut buf, 100)));
+        assert_eq!(messages, received);
+        let err = d2.recv_many(&mut received, &mut buf[..99], 100).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // datagrams longer than a slot are truncated
+        or_panic!(d1.send(&[1; 200]));
+        received.clear();
+        assert_eq!(1, or_panic!(d2.recv_many(&mut received, &mut buf, 100)));
+        assert_eq!(received[0].data, vec![1; 128]);
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let sender_path = dir.path().join("sender");
+        let receiver = or_panic!(UnixDatagram::bind(&path));
+        let sender = or_panic!(UnixDatagram::bind(&sender_path));
+        let messages = vec![DatagramMsg {
+                                data: b"hello".to_vec(),
+                                addr: Some(or_panic!(SocketAddr::from_path(&path))),
+                            },
+                            DatagramMsg {
+                                data: b"world".to_vec(),
+                                addr: Some(or_panic!(SocketAddr::from_path(&path))),
+                            }];
+        assert_eq!(2, or_panic!(sender.send_many(&messages)));
+
+        received.clear();
+        assert_eq!(2, or_panic!(receiver.recv_many(&mut received, &mut buf, 10)));
+        assert_eq!(b"hello", &received[0].data[..]);
+        assert_eq!(b"world", &received[1].data[..]);
+        let from = received[0].addr.as_ref().unwrap();
+        assert_eq!(Some(&*sender_path), from.address().as_pathname());
+    }
+
+    #[test]
+    fn try_accept() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        or_panic!(listener.set_nonblocking(true));
+        assert!(or_panic!(listener.try_accept()).is_none());
+        assert!(or_panic!(listener.try_accept_addr()).is_none());
+
+        let _s1 = or_panic!(UnixStream::connect(&socket_path));
+        let _s2 = or_panic!(UnixStream::connect(&socket_path));
+        assert!(or_panic!(listener.try_accept()).is_some());
+        let (_, addr) = or_panic!(listener.try_accept_addr()).unwrap();
+        assert!(addr.address().is_unnamed());
+        assert!(or_panic!(listener.try_accept()).is_none());
+    }
+
+    #[test]
+    fn eintr() {
+        use std::os::unix::thread::JoinHandleExt;
+
+        extern "C" fn handler(_: libc::c_int) {}
+
+        // Without SA_RESTART the signal interrupts the blocking calls
+        let old_action = unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            let mut old_action: libc::sigaction = mem::zeroed();
+            or_panic!(cvt(libc::sigaction(libc::SIGUSR1, &action, &mut old_action)));
+            old_action
+        };
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+
+        let thread = thread::spawn(move || {
+            or_panic!(listener.accept());
+        });
+        for _ in 0..5 {
+            thread::sleep(std::time::Duration::from_millis(10));
+            assert_eq!(0, unsafe { libc::pthread_kill(thread.as_pthread_t(), libc::SIGUSR1) });
+        }
+        or_panic!(UnixStream::connect(&socket_path));
+        thread.join().unwrap();
+
+        // A full backlog blocks connect on Linux, while other platforms
+        // refuse the connection instead
+        #[cfg(target_os = "linux")]
+        {
+            let socket_path = dir.path().join("sock2");
+            let listener = or_panic!(ListenerOptions::new().backlog(0).bind(&socket_path));
+            let _queued = or_panic!(UnixStream::connect(&socket_path));
+
+            let thread = thread::spawn(move || {
+                let mut stream = or_panic!(UnixStream::connect(&socket_path));
+                or_panic!(stream.write_all(b"x"));
+            });
+            for _ in 0..5 {
+                thread::sleep(std::time::Duration::from_millis(10));
+                assert_eq!(0, unsafe { libc::pthread_kill(thread.as_pthread_t(), libc::SIGUSR1) });
+            }
+            or_panic!(listener.accept());
+            let mut stream = or_panic!(listener.accept());
+            let mut buf = [0; 1];
+            or_panic!(stream.read_exact(&mut buf));
+            thread.join().unwrap();
+        }
+
+        unsafe {
+            or_panic!(cvt(libc::sigaction(libc::SIGUSR1, &old_action, ptr::null_mut())));
+        }
+    }
+
+    #[test]
+    fn accept_flags() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+
+        let _s1 = or_panic!(UnixStream::connect(&socket_path));
+        let accepted = or_panic!(listener.accept());
+        assert!(is_cloexec(&accepted));
+        assert!(!or_panic!(accepted.nonblocking()));
+
+        let _s2 = or_panic!(UnixStream::connect(&socket_path));
+        let (accepted, _) = or_panic!(listener.accept_addr());
+        assert!(is_cloexec(&accepted));
+
+        let _s3 = or_panic!(UnixStream::connect(&socket_path));
+        let accepted = or_panic!(listener.accept_nonblocking());
+        assert!(is_cloexec(&accepted));
+        assert!(or_panic!(accepted.nonblocking()));
+    }
+
+    #[test]
+    fn abstract_path() {
+        assert!(AbstractPath::new(b"name").is_err());
+        assert!(AbstractPath::new(b"\0na\0me").is_err());
+        assert!(AbstractPath::new(&[0; 200]).is_err());
+        assert!(AbstractPathBuf::new(b"na\0me").is_err());
+
+        let path = or_panic!(AbstractPath::new(b"\0name"));
+        assert_eq!(b"name", path.as_bytes());
+        assert_eq!(b"\0name", path.as_sockaddr_bytes());
+        assert_eq!("@name", path.to_string());
+        let buf = or_panic!(AbstractPathBuf::new(b"name"));
+        assert_eq!(path, buf.as_abstract_path());
+        assert_eq!(buf, path.to_owned());
+    }
+
+    #[test]
+    fn abstract_path_validate() {
+        or_panic!(AbstractPath::validate(&[b'a'; 107]));
+        assert!(AbstractPath::validate(&[b'a'; 108]).is_err());
+        let err = AbstractPath::validate(&[b'a'; 109]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        or_panic!(AbstractPath::validate(b"na\0me"));
+        assert!(AbstractPath::validate_printable(b"na\0me").is_err());
+        assert!(AbstractPath::validate_printable(b"name\x7f").is_err());
+        or_panic!(AbstractPath::validate_printable(b"unix_socket name"));
+        assert!(AbstractPath::validate_printable(&[b'a'; 108]).is_err());
+
+        let buf: AbstractPathBuf = or_panic!("@na\\x01me".parse());
+        assert_eq!(buf.as_bytes(), b"na\x01me");
+        assert_eq!(or_panic!(buf.to_string().parse::<AbstractPathBuf>()), buf);
+        assert!("name".parse::<AbstractPathBuf>().is_err());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn abstract_path_sockets() {
+        let name = format!("unix_socket abstract_path_sockets {}", std::process::id());
+        let path = or_panic!(AbstractPathBuf::new(name.as_bytes()));
+        let listener = or_panic!(UnixListener::bind_abstract(&path));
+        assert_eq!(Some(path.as_bytes()),
+                   or_panic!(listener.local_addr()).address().as_abstract());
+
+        let mut s1 = or_panic!(UnixStream::connect_abstract(&path));
+        let mut s2 = or_panic!(listener.accept());
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        let name = format!("\0unix_socket abstract_path_datagram {}", std::process::id());
+        let path = or_panic!(AbstractPath::new(name.as_bytes()));
+        let receiver = or_panic!(UnixDatagram::bind_abstract(path));
+        let sender = or_panic!(UnixDatagram::unbound());
+        or_panic!(sender.send_to_abstract(b"hello", path));
+        buf = [0; 5];
+        or_panic!(receiver.recv(&mut buf));
+        assert_eq!(b"hello", &buf);
+    }
+
+    #[test]
+    fn addr_overloads() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("sock");
+        let addr = or_panic!(SocketAddr::from_path(&socket_path));
+
+        let listener = or_panic!(UnixListener::bind_addr(&addr));
+        let mut s1 = or_panic!(UnixStream::connect_addr(&or_panic!(listener.local_addr())));
+        let mut s2 = or_panic!(listener.accept());
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        let dgram_addr = or_panic!(SocketAddr::from_path(dir.path().join("dgram")));
+        let sender_addr = or_panic!(SocketAddr::from_path(dir.path().join("sender")));
+        let receiver = or_panic!(UnixDatagram::bind_addr(&dgram_addr));
+        let sender = or_panic!(UnixDatagram::bind_addr(&sender_addr));
+        or_panic!(sender.send_to_addr(b"hello", &dgram_addr));
+        let (_, from) = or_panic!(receiver.recv_from(&mut buf));
+        assert_eq!(sender_addr, from);
+        or_panic!(receiver.send_to_addr(b"world", &from));
+        or_panic!(sender.recv(&mut buf));
+        assert_eq!(b"world", &buf);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn abstract_addr_overloads() {
+        let name = format!("unix_socket abstract_addr_overloads {}", std::process::id());
+        let addr = or_panic!(SocketAddr::from_abstract(name.as_bytes()));
+        let listener = or_panic!(UnixListener::bind_addr(&addr));
+        assert_eq!(addr, or_panic!(listener.local_addr()));
+        or_panic!(UnixStream::connect_addr(&addr));
+        or_panic!(listener.accept());
+    }
+
+    #[test]
+    fn linger() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(None, or_panic!(s1.linger()));
+        or_panic!(s1.set_linger(Some(std::time::Duration::from_secs(5))));
+        assert_eq!(Some(std::time::Duration::from_secs(5)), or_panic!(s1.linger()));
+        or_panic!(s1.set_linger(None));
+        assert_eq!(None, or_panic!(s1.linger()));
+
+        or_panic!(s1.set_linger(Some(std::time::Duration::from_secs(0))));
+        assert_eq!(Some(std::time::Duration::from_secs(0)), or_panic!(s1.linger()));
+
+        // a zero timeout does not discard data already written
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            or_panic!(s1.write_all(b"hello"));
+            drop(s1);
+
+            let mut buf = vec![];
+            or_panic!(s2.read_to_end(&mut buf));
+            assert_eq!(b"hello", &buf[..]);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore)]
+    fn oob() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        assert!(!or_panic!(s2.oobinline()));
+
+        or_panic!(s1.write_all(b"hello"));
+        match s1.send_oob(b'!') {
+            // Kernels before 5.15 or built without CONFIG_AF_UNIX_OOB
+            Err(ref e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+            res => or_panic!(res),
+        }
+        or_panic!(s1.write_all(b"world"));
+
+        let mut buf = [0; 16];
+        assert_eq!(5, or_panic!(s2.read(&mut buf)));
+        assert_eq!(b"hello", &buf[..5]);
+        assert_eq!(b'!', or_panic!(s2.recv_oob()));
+        assert_eq!(5, or_panic!(s2.read(&mut buf)));
+        assert_eq!(b"world", &buf[..5]);
+
+        or_panic!(s2.set_oobinline(true));
+        assert!(or_panic!(s2.oobinline()));
+        or_panic!(s1.write_all(b"hello"));
+        or_panic!(s1.send_oob(b'!'));
+        let mut buf = [0; 6];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(b"hello!", &buf);
+    }
+
+    #[test]
+    fn recv_from_full() {
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d1.send(&[1; 1000]));
+        let mut buf = [0; 16];
+        let info = or_panic!(d2.recv_from_full(&mut buf));
+        assert_eq!(16, info.bytes_copied);
+        assert_eq!([1; 16], buf);
+        if cfg!(target_os = "linux") {
+            assert_eq!(1000, info.true_size);
+            assert!(info.is_truncated());
+        }
+
+        or_panic!(d1.send(b"hello"));
+        let info = or_panic!(d2.recv_from_full(&mut buf));
+        assert_eq!(5, info.bytes_copied);
+        assert_eq!(5, info.true_size);
+        assert!(!info.is_truncated());
+    }
+
+    #[test]
+    fn available_bytes() {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        use linux::LinuxUnixStreamExt;
+
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(0, or_panic!(s2.available_bytes()));
+        or_panic!(s1.write_all(b"hello"));
+        or_panic!(s1.write_all(b"world"));
+        assert_eq!(10, or_panic!(s2.available_bytes()));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert!(or_panic!(s1.outgoing_queued_bytes()) >= 10);
+        let mut buf = [0; 10];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(0, or_panic!(s2.available_bytes()));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(0, or_panic!(s1.outgoing_queued_bytes()));
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d1.send(b"hello"));
+        or_panic!(d1.send(b"world!"));
+        if cfg!(target_os = "linux") {
+            assert_eq!(5, or_panic!(d2.available_bytes()));
+        }
+    }
+
+    #[test]
+    fn read_full() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_full(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        let thread = thread::spawn(move || {
+            or_panic!(s1.write_all(b"wor"));
+            thread::sleep(std::time::Duration::from_millis(10));
+            or_panic!(s1.write_all(b"ld"));
+        });
+        or_panic!(s2.read_full(&mut buf));
+        assert_eq!(b"world", &buf);
+        thread.join().unwrap();
+
+        let mut buf = [0; 2];
+        match s2.read_full(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn buf_stream() {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        use linux::LinuxUnixStreamExt;
+
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            or_panic!(s1.write_all(b"hel"));
+            thread::sleep(std::time::Duration::from_millis(10));
+            or_panic!(s1.write_all(b"lo\nwor"));
+            thread::sleep(std::time::Duration::from_millis(10));
+            or_panic!(s1.write_all(b"ld\n"));
+
+            let mut buf = [0; 4];
+            or_panic!(s1.read_exact(&mut buf));
+            assert_eq!(b"ping", &buf);
+            or_panic!(s1.write_all(b"pong\n"));
+            s1
+        });
+
+        let mut stream = BufUnixStream::with_capacities(s2, 4, 16);
+        let mut line = String::new();
+        or_panic!(stream.read_line(&mut line));
+        assert_eq!("hello\n", line);
+        line.clear();
+        or_panic!(stream.read_line(&mut line));
+        assert_eq!("world\n", line);
+
+        or_panic!(stream.write_all(b"ping"));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(0, or_panic!(stream.get_ref().outgoing_queued_bytes()));
+        line.clear();
+        or_panic!(stream.read_line(&mut line));
+        assert_eq!("pong\n", line);
+        let mut s1 = thread.join().unwrap();
+
+        or_panic!(stream.write_all(b"bye"));
+        let s2 = or_panic!(stream.into_inner());
+        let mut buf = [0; 3];
+        or_panic!(s1.read_exact(&mut buf));
+        assert_eq!(b"bye", &buf);
+
+        // a zero read capacity must not look like end of file
+        let mut stream = BufUnixStream::with_capacities(s2, 0, 0);
+        or_panic!(s1.write_all(b"hi\n"));
+        let mut line = String::new();
+        or_panic!(stream.read_line(&mut line));
+        assert_eq!("hi\n", line);
+    }
+
+    #[test]
+    fn framed_stream() {
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            let mut stream = FramedUnixStream::new(s1);
+            for i in 0..1000 {
+                or_panic!(stream.send_frame(&vec![i as u8; i % 300]));
+            }
+        });
+
+        let mut stream = FramedUnixStream::with_max_frame_size(s2, 300);
+        for i in 0..500 {
+            assert_eq!(vec![i as u8; i % 300], or_panic!(stream.recv_frame()));
+        }
+        let mut buf = vec![];
+        for i in 500..1000 {
+            or_panic!(stream.recv_frame_into(&mut buf));
+            assert_eq!(vec![i as u8; i % 300], buf);
+        }
+        thread.join().unwrap();
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(FramedUnixStream::new(s1).send_frame(&[0; 101]));
+        let mut stream = FramedUnixStream::with_max_frame_size(s2, 100);
+        match stream.recv_frame() {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_socket_addr() {
+        extern crate serde_json;
+
+        let addrs = [
+            (or_panic!(SocketAddr::from_path("/some/path")),
+             r#"{"type":"pathname","path":"/some/path"}"#),
+            (or_panic!(SocketAddr::from_abstract(b"ab\0\xff")),
+             r#"{"type":"abstract","name":"616200ff"}"#),
+            (SocketAddr::unnamed(), r#"{"type":"unnamed"}"#),
+        ];
+        for &(ref addr, json) in &addrs {
+            assert_eq!(json, or_panic!(serde_json::to_string(addr)));
+            assert_eq!(*addr, or_panic!(serde_json::from_str::<SocketAddr>(json)));
+        }
+
+        assert!(serde_json::from_str::<SocketAddr>(r#"{"type":"abstract","name":"6"}"#).is_err());
+        assert!(serde_json::from_str::<SocketAddr>(r#"{"type":"pathname"}"#).is_err());
+        assert!(serde_json::from_str::<SocketAddr>(r#"{"type":"inet"}"#).is_err());
+    }
+
+    #[test]
+    fn datagram_timestamp() {
+        fn assert_recent(time: std::time::SystemTime) {
+            let now = std::time::SystemTime::now();
+            let diff = match now.duration_since(time) {
+                Ok(diff) => diff,
+                Err(e) => e.duration(),
+            };
+            assert!(diff < std::time::Duration::from_millis(100), "{:?} vs {:?}", time, now);
+        }
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        let mut buf = [0; 5];
+
+        or_panic!(d1.send(b"hello"));
+        let (count, _, time) = or_panic!(d2.recv_from_timestamp(&mut buf));
+        assert_eq!(5, count);
+        assert_eq!(None, time);
+
+        or_panic!(d2.set_timestamp(true));
+        or_panic!(d1.send(b"hello"));
+        let (count, addr, time) = or_panic!(d2.recv_from_timestamp(&mut buf));
+        assert_eq!(5, count);
+        assert_eq!(b"hello", &buf);
+        assert_eq!(SocketAddr::unnamed(), addr);
+        assert_recent(time.unwrap());
+    }
+
+    #[test]
+    fn std_conversions() {
+        use std::convert::TryFrom;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+
+        let listener = std::os::unix::net::UnixListener::from(
+            or_panic!(UnixListener::bind(&path)));
+        let listener = UnixListener::from(listener);
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        let mut s1 = std::os::unix::net::UnixStream::from(s1);
+        let mut s2 = UnixStream::from(std::os::unix::net::UnixStream::from(s2));
+        or_panic!(s2.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s1.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        let stream = or_panic!(std::os::unix::net::UnixStream::connect(&path));
+        let (_, addr) = or_panic!(listener.accept_addr());
+        assert_eq!(SocketAddr::unnamed(), addr);
+        assert_eq!(or_panic!(listener.local_addr()),
+                   or_panic!(SocketAddr::try_from(or_panic!(stream.peer_addr()))));
+        assert_eq!(SocketAddr::unnamed(),
+                   or_panic!(SocketAddr::try_from(or_panic!(stream.local_addr()))));
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        let d1 = std::os::unix::net::UnixDatagram::from(d1);
+        let d2 = UnixDatagram::from(std::os::unix::net::UnixDatagram::from(d2));
+        or_panic!(d1.send(b"world"));
+        or_panic!(d2.recv(&mut buf));
+        assert_eq!(b"world", &buf);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn std_abstract_addr() {
+        use std::convert::TryFrom;
+        use std::os::linux::net::SocketAddrExt;
+
+        let addr = or_panic!(std::os::unix::net::SocketAddr::from_abstract_name(b"name\0"));
+        assert_eq!(or_panic!(SocketAddr::from_abstract(b"name\0")),
+                   or_panic!(SocketAddr::try_from(addr)));
+    }
+
+    #[test]
+    fn datagram_options() {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        use linux::LinuxUnixDatagramExt;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+
+        let options = DatagramOptions::new()
+                          .nonblocking(true)
+                          .cloexec(true)
+                          .send_buf(64 * 1024)
+                          .recv_buf(64 * 1024);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let options = options.passcred(true);
+
+        let socket = or_panic!(options.clone().bind(&path));
+        assert_eq!(Some(&*path), or_panic!(socket.local_addr()).address().as_pathname());
+        assert!(or_panic!(socket.send_buffer_size()) >= 64 * 1024);
+        assert!(or_panic!(socket.recv_buffer_size()) >= 64 * 1024);
+        assert!(is_cloexec(&socket));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert!(or_panic!(socket.passcred()));
+        let mut buf = [0; 1];
+        match socket.recv(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let unbound = or_panic!(options.unbound());
+        assert_eq!(SocketAddr::unnamed(), or_panic!(unbound.local_addr()));
+        assert!(or_panic!(unbound.send_buffer_size()) >= 64 * 1024);
+        or_panic!(unbound.send_to(b"a", &path));
+        assert_eq!(1, or_panic!(socket.recv(&mut buf)));
+
+        let socket = or_panic!(DatagramOptions::new().unbound());
+        assert!(!is_cloexec(&socket));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert!(!or_panic!(socket.passcred()));
+    }
+
+    #[test]
+    fn sun_path_max() {
+        let addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+        assert_eq!(addr.sun_path.len(), SUN_PATH_MAX);
+        assert_eq!(SUN_PATH_MAX, ::sun_path_max());
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(108, SUN_PATH_MAX);
+        } else if cfg!(any(target_os = "macos", target_os = "ios", target_os = "freebsd")) {
+            assert_eq!(104, SUN_PATH_MAX);
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let base = dir.path().as_os_str().len() + 1;
+        let path = dir.path().join(vec!["a"; SUN_PATH_MAX - 1 - base].concat());
+        or_panic!(UnixListener::bind(&path));
+        let path = dir.path().join(vec!["b"; SUN_PATH_MAX - base].concat());
+        assert!(UnixListener::bind(&path).is_err());
+    }
+
+    #[test]
+    fn long_path() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let socket_path = dir.path().join("asdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasd\
+                                           fasdfasasdfasdfasdasdfasdfasdfadfasdfasdfasdfasdfasdf");
+        match UnixStream::connect(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+
+        match UnixListener::bind(&socket_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    #[test]
+    fn unix_socket_error() {
+        use std::error::Error;
+
+        fn boxed<T>(r: Result<T, UnixSocketError>) -> Box<dyn Error> {
+            match r {
+                Ok(_) => panic!("unexpected success"),
+                Err(e) => Box::new(e),
+            }
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let err = boxed(UnixStream::connect_checked(dir.path().join("sock")));
+        match err.downcast_ref::<UnixSocketError>() {
+            Some(UnixSocketError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(err.source().is_some());
+
+        let path = dir.path().join(vec!["a"; SUN_PATH_MAX].concat());
+        let len = path.as_os_str().len();
+        let err = boxed(UnixListener::bind_checked(&path));
+        match err.downcast_ref::<UnixSocketError>() {
+            Some(&UnixSocketError::PathTooLong { len: l, max }) => {
+                assert_eq!(l, len);
+                assert_eq!(max, SUN_PATH_MAX - 1);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        let err = or_panic!(err.downcast::<UnixSocketError>());
+        let err = io::Error::from(*err);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = boxed(AbstractPath::new_checked(b"\0foo\0bar"));
+        match err.downcast_ref::<UnixSocketError>() {
+            Some(UnixSocketError::InvalidAbstractName(_)) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        let cred = or_panic!(s1.check_peer(|_| true));
+        let err = boxed(s1.check_peer(|_| false));
+        match err.downcast_ref::<UnixSocketError>() {
+            Some(UnixSocketError::AuthenticationFailed(c)) => assert_eq!(*c, cred),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let mut s2 = FramedUnixStream::with_max_frame_size(s2, 4);
+        let mut s1 = FramedUnixStream::new(s1);
+        or_panic!(s1.send_frame(b"hello"));
+        let err = boxed(s2.recv_frame_checked());
+        match err.downcast_ref::<UnixSocketError>() {
+            Some(UnixSocketError::FrameTooLarge { size: 5, max: 4 }) => {}
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn socket_addr_try_from() {
+        use std::convert::TryFrom;
+        use std::path::PathBuf;
+
+        let addr = or_panic!(SocketAddr::try_from(PathBuf::from("/tmp/sock")));
+        assert_eq!(addr.address(), AddressKind::Pathname(Path::new("/tmp/sock")));
+        let addr = or_panic!(SocketAddr::try_from(Path::new("/tmp/sock")));
+        assert_eq!(addr.address(), AddressKind::Pathname(Path::new("/tmp/sock")));
+        let addr = or_panic!(SocketAddr::try_from("/tmp/sock".to_owned()));
+        assert_eq!(addr.address(), AddressKind::Pathname(Path::new("/tmp/sock")));
+        let addr = or_panic!(SocketAddr::try_from("relative\\sock"));
+        assert_eq!(addr.address(), AddressKind::Pathname(Path::new("relative\\sock")));
+
+        let addr = or_panic!(SocketAddr::try_from("@foo".to_owned()));
+        assert_eq!(addr.address(), AddressKind::Abstract(b"foo"));
+        let addr = or_panic!(SocketAddr::try_from("@foo\\x00"));
+        assert_eq!(addr.address(), AddressKind::Abstract(b"foo\\x00"));
+
+        let long = vec!["a"; SUN_PATH_MAX].concat();
+        let err = SocketAddr::try_from(PathBuf::from(&long)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains(&format!("limit is {}", SUN_PATH_MAX - 1)), "{}", err);
+        let err = SocketAddr::try_from(Path::new(&long)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = SocketAddr::try_from(long.clone()).unwrap_err();
+        assert!(err.to_string().contains(&format!("{} bytes", SUN_PATH_MAX)), "{}", err);
+        let err = SocketAddr::try_from(&*format!("@{}", long)).unwrap_err();
+        assert!(err.to_string().contains(&format!("limit is {}", SUN_PATH_MAX)), "{}", err);
+    }
+
+    #[test]
+    fn socket_addr_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&path));
+        let addr = or_panic!(listener.local_addr());
+        assert_eq!(addr.as_os_str(), Some(path.as_os_str()));
+        assert_eq!(addr.as_abstract_bytes(), None);
+        assert_eq!(addr.path_byte_len(), path.as_os_str().len());
+        assert!(addr.as_raw_bytes().starts_with(path.as_os_str().as_bytes()));
+
+        let addr = or_panic!(SocketAddr::from_path("/tmp/sock"));
+        assert_eq!(addr.as_os_str(), Some(OsStr::new("/tmp/sock")));
+        assert_eq!(addr.as_raw_bytes(), b"/tmp/sock\0");
+        assert_eq!(addr.path_byte_len(), 9);
+
+        let addr = or_panic!(SocketAddr::from_abstract(b"foo"));
+        assert_eq!(addr.as_os_str(), None);
+        assert_eq!(addr.as_abstract_bytes(), Some(&b"foo"[..]));
+        assert_eq!(addr.as_raw_bytes(), b"\0foo");
+        assert_eq!(addr.path_byte_len(), 3);
+
+        let addr = SocketAddr::unnamed();
+        assert_eq!(addr.as_os_str(), None);
+        assert_eq!(addr.as_abstract_bytes(), None);
+        assert_eq!(addr.as_raw_bytes(), b"");
+        assert_eq!(addr.path_byte_len(), 0);
+    }
+
+    #[test]
+    fn display_sockets() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&path));
+        assert_eq!(listener.to_string(), format!("UnixListener {{ local: {} }}", path.display()));
+
+        let stream = or_panic!(UnixStream::connect(&path));
+        assert_eq!(stream.to_string(),
+                   format!("UnixStream {{ local: (unnamed), peer: {} }}", path.display()));
+
+        let path = dir.path().join("dgram");
+        let datagram = or_panic!(UnixDatagram::bind(&path));
+        assert_eq!(datagram.to_string(), format!("UnixDatagram {{ local: {} }}", path.display()));
+
+        if cfg!(target_os = "linux") {
+            let name = format!("unix_socket_display_{}", std::process::id());
+            let listener = or_panic!(UnixListener::bind(format!("\0{}", name)));
+            assert_eq!(listener.to_string(), format!("UnixListener {{ local: @{} }}", name));
+        }
+    }
+
+    #[test]
+    fn ascii_escaped() {
+        assert_eq!(AsciiEscaped::new(b"hello world").to_string(), "\"hello world\"");
+        assert_eq!(AsciiEscaped(b"\0\x01\t\r\n\x7f").to_string(),
+                   "\"\\x00\\x01\\t\\r\\n\\x7f\"");
+        assert_eq!(AsciiEscaped(b"\x80\xff").to_string(), "\"\\x80\\xff\"");
+        assert_eq!(AsciiEscaped(b"'\"\\").to_string(), "\"\\'\\\"\\\\\"");
+        assert_eq!(format!("{:?}", AsciiEscaped(b"a\n")), "\"a\\n\"");
+
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let expected = bytes.iter()
+                            .cloned()
+                            .flat_map(::std::ascii::escape_default)
+                            .map(|b| b as char)
+                            .collect::<String>();
+        assert_eq!(AsciiEscaped(&bytes).to_string(), format!("\"{}\"", expected));
+    }
+
+    #[test]
+    fn stream_lines() {
+        let (s1, mut s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            for i in 0..10 {
+                let ending = if i % 2 == 0 { "\n" } else { "\r\n" };
+                or_panic!(write!(s2, "line {}{}", i, ending));
             }
+        });
+
+        let lines = s1.lines().collect::<io::Result<Vec<_>>>();
+        let expected = (0..10).map(|i| format!("line {}", i)).collect::<Vec<_>>();
+        assert_eq!(or_panic!(lines), expected);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn socket_addr_raw() {
+        for addr in &[or_panic!(SocketAddr::from_path("/tmp/sock")),
+                      or_panic!(SocketAddr::from_abstract(b"name")),
+                      SocketAddr::unnamed()] {
+            let raw = *addr.raw_sockaddr_un();
+            assert_eq!(raw.sun_family, libc::AF_UNIX as libc::sa_family_t);
+            let copy = or_panic!(SocketAddr::from_raw(raw, addr.raw_len()));
+            assert_eq!(&copy, addr);
+            assert_eq!(copy.raw_len(), addr.raw_len());
         }
+
+        let addr = or_panic!(SocketAddr::from_path("/tmp/sock"));
+        let mut raw = *addr.raw_sockaddr_un();
+        assert!(SocketAddr::from_raw(raw, 1).is_err());
+        let len = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+        assert!(SocketAddr::from_raw(raw, len + 1).is_err());
+        raw.sun_family = libc::AF_INET as libc::sa_family_t;
+        let err = SocketAddr::from_raw(raw, addr.raw_len()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn socket_addr_ord() {
+        let mut addrs = vec![or_panic!(SocketAddr::from_path("/tmp/b")),
+                             or_panic!(SocketAddr::from_abstract(b"b")),
+                             SocketAddr::unnamed(),
+                             or_panic!(SocketAddr::from_path("/tmp/a")),
+                             or_panic!(SocketAddr::from_abstract(b"a"))];
+        addrs.sort();
+        let expected = vec![SocketAddr::unnamed(),
+                            or_panic!(SocketAddr::from_abstract(b"a")),
+                            or_panic!(SocketAddr::from_abstract(b"b")),
+                            or_panic!(SocketAddr::from_path("/tmp/a")),
+                            or_panic!(SocketAddr::from_path("/tmp/b"))];
+        assert_eq!(addrs, expected);
+
+        let a = or_panic!(SocketAddr::from_path("/tmp/a"));
+        assert_eq!(a.partial_cmp(&a.clone()), Some(::std::cmp::Ordering::Equal));
+        assert!(SocketAddr::unnamed() < a);
+    }
+
+    #[test]
+    fn shutdown_halves() {
+        let (mut a, mut b) = or_panic!(UnixStream::unnamed());
+        assert!(!or_panic!(b.is_readable()));
+        assert!(or_panic!(a.is_writable()));
+        or_panic!(a.write_all(b"x"));
+        assert!(or_panic!(b.is_readable()));
+        let mut buf = [0; 1];
+        or_panic!(b.read_exact(&mut buf));
+
+        or_panic!(a.shutdown_write());
+        assert_eq!(a.write(b"x").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert!(or_panic!(b.is_readable()));
+        assert_eq!(or_panic!(b.read(&mut buf)), 0);
+        or_panic!(b.shutdown_read());
+
+        let (a, b) = or_panic!(UnixDatagram::pair());
+        assert!(!or_panic!(b.is_readable()));
+        or_panic!(a.send(b"x"));
+        assert!(or_panic!(b.is_readable()));
+        or_panic!(a.shutdown_write());
+        assert_eq!(a.send(b"x").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(or_panic!(b.recv(&mut buf)), 1);
+        or_panic!(b.shutdown_read());
+        assert!(or_panic!(b.is_readable()));
+        assert_eq!(or_panic!(b.recv(&mut buf)), 0);
+
+        let (a, b) = or_panic!(UnixSeqpacket::pair());
+        assert!(or_panic!(a.is_writable()));
+        or_panic!(a.shutdown_write());
+        assert_eq!(a.send(b"x").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert!(or_panic!(b.is_readable()));
+        or_panic!(b.shutdown_read());
+    }
+
+    #[test]
+    fn listener_reuseaddr() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let listener = or_panic!(UnixListener::bind(dir.path().join("sock")));
+        assert!(!or_panic!(listener.reuseaddr()));
+        or_panic!(listener.set_reuseaddr(true));
+        assert!(or_panic!(listener.reuseaddr()));
+        or_panic!(listener.set_reuseaddr(false));
+        assert!(!or_panic!(listener.reuseaddr()));
+    }
+
+    #[test]
+    fn datagram_disconnect() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+        let path3 = dir.path().join("sock3");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        let sock3 = or_panic!(UnixDatagram::bind(&path3));
+
+        or_panic!(sock3.connect(&path1));
+        or_panic!(sock3.send(b"one"));
+        or_panic!(sock3.disconnect());
+        assert!(!or_panic!(sock3.is_connected()));
+        assert!(sock3.send(b"two").is_err());
+
+        or_panic!(sock3.send_to(b"three", &path2));
+        let mut buf = [0; 5];
+        let (len, addr) = or_panic!(sock2.recv_from(&mut buf));
+        assert_eq!(&buf[..len], b"three");
+        assert_eq!(addr.address(), AddressKind::Pathname(&path3));
+        let len = or_panic!(sock1.recv(&mut buf));
+        assert_eq!(&buf[..len], b"one");
+
+        or_panic!(sock2.send_to(b"four", &path3));
+        let len = or_panic!(sock3.recv(&mut buf));
+        assert_eq!(&buf[..len], b"four");
     }
 
     #[test]
-    fn basic() {
+    fn datagram_recv_meta() {
         let dir = or_panic!(TempDir::new("unix_socket"));
-        let socket_path = dir.path().join("sock");
-        let msg1 = b"hello";
-        let msg2 = b"world!";
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+        let (file, data) = fd_fixture(&dir);
 
-        let listener = or_panic!(UnixListener::bind(&socket_path));
-        let thread = thread::spawn(move || {
-            let mut stream = or_panic!(listener.accept());
-            let mut buf = [0; 5];
-            or_panic!(stream.read(&mut buf));
-            assert_eq!(&msg1[..], &buf[..]);
-            or_panic!(stream.write_all(msg2));
-        });
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        or_panic!(sock1.connect(&path2));
 
-        let mut stream = or_panic!(UnixStream::connect(&socket_path));
-        or_panic!(stream.write_all(msg1));
-        let mut buf = vec![];
-        or_panic!(stream.read_to_end(&mut buf));
-        assert_eq!(&msg2[..], &buf[..]);
-        drop(stream);
+        or_panic!(sock1.send_with_ancillary(b"hello", &data));
 
-        thread.join().unwrap();
+        let mut buf = [0; 3];
+        let meta = or_panic!(sock2.recv_meta(&mut buf, 1024));
+        assert_eq!(meta.bytes, 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(meta.addr.as_ref().map(|a| a.address()), Some(AddressKind::Pathname(&path1)));
+        assert!(meta.was_truncated());
+        assert!(!meta.control_truncated());
+        check_fd_fixture(&file, &meta.ancillary);
+
+        or_panic!(sock1.send_with_ancillary(b"world", &data));
+        let mut buf = [0; 10];
+        let meta = or_panic!(sock2.recv_meta(&mut buf, 0));
+        assert_eq!(meta.bytes, 5);
+        assert!(!meta.was_truncated());
+        assert!(meta.control_truncated());
+        assert!(meta.ancillary.is_truncated());
+
+        let unbound = or_panic!(UnixDatagram::unbound());
+        or_panic!(unbound.send_to(b"!", &path2));
+        let meta = or_panic!(sock2.recv_meta(&mut buf, 1024));
+        assert_eq!(meta.bytes, 1);
+        assert!(meta.addr.is_none());
+        assert_eq!(meta.ancillary.entries().count(), 0);
     }
 
     #[test]
-    fn unnamed() {
-        let msg1 = b"hello";
-        let msg2 = b"world!";
+    fn send_meta() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+        let (file, ancillary) = fd_fixture(&dir);
 
-        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
-        let thread = thread::spawn(move || {
-            // s1 must be moved in or the test will hang!
-            let mut buf = [0; 5];
-            or_panic!(s1.read(&mut buf));
-            assert_eq!(&msg1[..], &buf[..]);
-            or_panic!(s1.write_all(msg2));
-        });
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
 
-        or_panic!(s2.write_all(msg1));
-        let mut buf = vec![];
-        or_panic!(s2.read_to_end(&mut buf));
-        assert_eq!(&msg2[..], &buf[..]);
-        drop(s2);
+        let addr = or_panic!(SocketAddr::from_path(&path2));
+        let msg = SendMsg {
+            data: b"hello",
+            addr: Some(&addr),
+            ancillary: &ancillary,
+            flags: 0,
+        };
+        assert_eq!(or_panic!(sock1.send_meta(&msg)), 5);
 
-        thread.join().unwrap();
+        let mut buf = [0; 10];
+        let meta = or_panic!(sock2.recv_meta(&mut buf, 1024));
+        assert_eq!(&buf[..meta.bytes], b"hello");
+        assert_eq!(meta.addr.as_ref().map(|a| a.address()), Some(AddressKind::Pathname(&path1)));
+        check_fd_fixture(&file, &meta.ancillary);
+
+        let (s1, mut s2) = or_panic!(UnixStream::unnamed());
+        let msg = SendMsg {
+            data: b"world",
+            addr: None,
+            ancillary: &AncillaryData::new(),
+            flags: 0,
+        };
+        assert_eq!(or_panic!(s1.send_meta(&msg)), 5);
+        or_panic!(s2.read_exact(&mut buf[..5]));
+        assert_eq!(&buf[..5], b"world");
     }
 
     #[test]
-    #[cfg_attr(not(target_os = "linux"), ignore)]
-    fn abstract_address() {
-        let socket_path = "\0the path";
-        let msg1 = b"hello";
-        let msg2 = b"world!";
+    fn stream_msg_vectored() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let (file, ancillary) = fd_fixture(&dir);
+        let (s1, mut s2) = or_panic!(UnixStream::unnamed());
+
+        let bufs = [io::IoSlice::new(b"head"), io::IoSlice::new(b"body!")];
+        assert_eq!(or_panic!(s1.sendmsg_vectored(&bufs, &ancillary, 0)), 9);
+
+        let mut header = [0; 4];
+        let mut body = [0; 5];
+        let mut received = AncillaryMessage::with_capacity(1024);
+        let count = {
+            let mut bufs = [io::IoSliceMut::new(&mut header), io::IoSliceMut::new(&mut body)];
+            or_panic!(s2.recvmsg_vectored(&mut bufs, &mut received, 0))
+        };
+        assert_eq!(count, 9);
+        assert_eq!(&header, b"head");
+        assert_eq!(&body, b"body!");
+        check_fd_fixture(&file, &received);
+
+        or_panic!(s1.sendmsg_vectored(&[io::IoSlice::new(b"x")], &AncillaryData::new(), 0));
+        let mut buf = [0; 1];
+        let count = or_panic!(s2.recvmsg_vectored(&mut [io::IoSliceMut::new(&mut buf)],
+                                                  &mut received,
+                                                  libc::MSG_PEEK));
+        assert_eq!(count, 1);
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(&buf, b"x");
+    }
 
-        let listener = or_panic!(UnixListener::bind(&socket_path));
-        let thread = thread::spawn(move || {
-            let mut stream = or_panic!(listener.accept());
-            let mut buf = [0; 5];
-            or_panic!(stream.read(&mut buf));
-            assert_eq!(&msg1[..], &buf[..]);
-            or_panic!(stream.write_all(msg2));
-        });
+    #[test]
+    fn try_read_write() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        let mut buf = [0; 4];
+        assert_eq!(s2.try_read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(s2.try_read_vectored(&mut [io::IoSliceMut::new(&mut buf)]).unwrap_err().kind(),
+                   io::ErrorKind::WouldBlock);
+        assert!(!or_panic!(s2.nonblocking()));
+
+        assert_eq!(or_panic!(s2.try_write(b"ab")), 2);
+        assert_eq!(or_panic!(s2.try_write_vectored(&[io::IoSlice::new(b"c"),
+                                                     io::IoSlice::new(b"d")])),
+                   2);
+        or_panic!(s1.read_exact(&mut buf));
+        assert_eq!(&buf, b"abcd");
+
+        or_panic!(s1.write_all(b"xy"));
+        assert_eq!(or_panic!(s2.try_read(&mut buf)), 2);
+        assert_eq!(&buf[..2], b"xy");
+    }
 
-        let mut stream = or_panic!(UnixStream::connect(&socket_path));
-        or_panic!(stream.write_all(msg1));
-        let mut buf = vec![];
-        or_panic!(stream.read_to_end(&mut buf));
-        assert_eq!(&msg2[..], &buf[..]);
-        drop(stream);
+    #[test]
+    fn socket_addr_raw_parts() {
+        let addr = or_panic!(SocketAddr::from_path("/tmp/sock"));
+        let (raw, len) = addr.to_raw_parts();
+        assert_eq!(len, addr.raw_len());
+        assert_eq!(or_panic!(SocketAddr::from_raw_parts(raw, len)), addr);
+
+        let mut raw = raw;
+        raw.sun_family = libc::AF_UNSPEC as libc::sa_family_t;
+        let err = SocketAddr::from_raw_parts(raw, len).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
 
-        thread.join().unwrap();
+    #[test]
+    fn datagram_recv_vectored_from() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        let mut head = [0; 10];
+        let mut tail = [0; 10];
+        assert_eq!(sock2.try_recv_vectored(&mut [io::IoSliceMut::new(&mut head)])
+                        .unwrap_err()
+                        .kind(),
+                   io::ErrorKind::WouldBlock);
+
+        let msg: Vec<u8> = (0..20).collect();
+        or_panic!(sock1.send_to(&msg, &path2));
+        let (count, addr) = {
+            let mut bufs = [io::IoSliceMut::new(&mut head), io::IoSliceMut::new(&mut tail)];
+            or_panic!(sock2.recv_vectored_from(&mut bufs))
+        };
+        assert_eq!(count, 20);
+        assert_eq!(&head[..], &msg[..10]);
+        assert_eq!(&tail[..], &msg[10..]);
+        assert_eq!(addr.address(), AddressKind::Pathname(&path1));
+
+        or_panic!(sock1.send_to(&msg, &path2));
+        let count = or_panic!(sock2.try_recv_vectored(&mut [io::IoSliceMut::new(&mut head)]));
+        assert_eq!(count, 10);
+        assert_eq!(&head[..], &msg[..10]);
+        assert_eq!(sock2.try_recv_vectored(&mut [io::IoSliceMut::new(&mut head)])
+                        .unwrap_err()
+                        .kind(),
+                   io::ErrorKind::WouldBlock);
     }
 
     #[test]
-    fn try_clone() {
+    fn datagram_send_vectored_to() {
         let dir = or_panic!(TempDir::new("unix_socket"));
-        let socket_path = dir.path().join("sock");
-        let msg1 = b"hello";
-        let msg2 = b"world";
+        let path = dir.path().join("sock");
 
-        let listener = or_panic!(UnixListener::bind(&socket_path));
-        let thread = thread::spawn(move || {
-            let mut stream = or_panic!(listener.accept());
-            or_panic!(stream.write_all(msg1));
-            or_panic!(stream.write_all(msg2));
-        });
+        let receiver = or_panic!(UnixDatagram::bind(&path));
+        let sender = or_panic!(UnixDatagram::unbound());
+        let bufs = [io::IoSlice::new(b"head"), io::IoSlice::new(b"body")];
+        assert_eq!(or_panic!(sender.send_vectored_to(&bufs, &path)), 8);
+        let addr = or_panic!(receiver.local_addr());
+        assert_eq!(or_panic!(sender.send_vectored_to_addr(&bufs, &addr)), 8);
 
-        let mut stream = or_panic!(UnixStream::connect(&socket_path));
-        let mut stream2 = or_panic!(stream.try_clone());
+        let mut buf = [0; 16];
+        for _ in 0..2 {
+            assert_eq!(or_panic!(receiver.recv(&mut buf)), 8);
+            assert_eq!(&buf[..8], b"headbody");
+        }
+    }
 
+    #[test]
+    fn stream_read_write_fd() {
+        let (mut s1, mut s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(or_panic!(unsafe { UnixStream::write_fd(s1.as_raw_fd(), b"hello") }), 5);
         let mut buf = [0; 5];
-        or_panic!(stream.read(&mut buf));
-        assert_eq!(&msg1[..], &buf[..]);
-        or_panic!(stream2.read(&mut buf));
-        assert_eq!(&msg2[..], &buf[..]);
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
 
-        thread.join().unwrap();
+        or_panic!(s2.write_all(b"world"));
+        assert_eq!(or_panic!(unsafe { UnixStream::read_fd(s1.as_raw_fd(), &mut buf) }), 5);
+        assert_eq!(&buf, b"world");
+
+        // the descriptor is still open
+        or_panic!(s1.write_all(b"!"));
+        or_panic!(s2.read_exact(&mut buf[..1]));
     }
 
     #[test]
-    fn iter() {
+    fn std_into_from_std() {
+        use std::os::unix::io::IntoRawFd;
+        use std::os::unix::net;
+
         let dir = or_panic!(TempDir::new("unix_socket"));
-        let socket_path = dir.path().join("sock");
+        let (file, ancillary) = fd_fixture(&dir);
+
+        let (std1, std2) = or_panic!(net::UnixStream::pair());
+        let s1 = or_panic!(UnixStream::try_from_std(std1));
+        let s2 = UnixStream::from_std(std2);
+        or_panic!(s1.send_with_ancillary(b"x", &ancillary));
+        let mut received = AncillaryMessage::with_capacity(1024);
+        let mut buf = [0; 1];
+        or_panic!(s2.recv_with_ancillary(&mut buf, &mut received));
+        check_fd_fixture(&file, &received);
+
+        let mut std1 = s1.into_std();
+        let mut std2 = s2.into_std();
+        or_panic!(std1.write_all(b"y"));
+        or_panic!(std2.read_exact(&mut buf));
+        assert_eq!(&buf, b"y");
+
+        let (d1, _) = or_panic!(net::UnixDatagram::pair());
+        let not_stream = unsafe { net::UnixStream::from_raw_fd(d1.into_raw_fd()) };
+        let err = UnixStream::try_from_std(not_stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let listener = or_panic!(net::UnixListener::bind(dir.path().join("sock")));
+        let listener = UnixListener::from_std(listener);
+        assert!(listener.into_std().local_addr().is_ok());
+        let (d1, d2) = or_panic!(net::UnixDatagram::pair());
+        let d1 = UnixDatagram::from_std(d1);
+        or_panic!(d1.send(b"z"));
+        or_panic!(d2.recv(&mut buf));
+        assert_eq!(&buf, b"z");
+        assert!(d1.into_std().peer_addr().is_ok());
+    }
 
-        let listener = or_panic!(UnixListener::bind(&socket_path));
-        let thread = thread::spawn(move || {
-            for stream in listener.incoming().take(2) {
-                let mut stream = or_panic!(stream);
-                let mut buf = [0];
-                or_panic!(stream.read(&mut buf));
+    #[test]
+    fn connect_error_message() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let err = UnixStream::connect(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), format!("Unix socket path not found: {}", path.display()));
+        assert_eq!(os_error(&err), Some(libc::ENOENT));
+
+        drop(or_panic!(UnixListener::bind(&path)));
+        let err = UnixStream::connect(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(err.to_string(),
+                   format!("Unix socket exists but no listener: {}", path.display()));
+        assert_eq!(os_error(&err), Some(libc::ECONNREFUSED));
+
+        fn os_error(err: &io::Error) -> Option<i32> {
+            let source = err.get_ref().and_then(|err| err.source());
+            let source = source.and_then(|err| err.downcast_ref::<io::Error>());
+            source.and_then(|err| err.raw_os_error())
+        }
+    }
+
+    // shutting down a listening socket to end the loop only works on Linux
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn accept_loop() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        fn handle(mut stream: UnixStream) {
+            let mut buf = [0; 1];
+            or_panic!(stream.read_exact(&mut buf));
+            or_panic!(stream.write_all(&[buf[0] * 2]));
+        }
+
+        fn run_clients(path: &Path) {
+            for i in 0..50u8 {
+                let mut stream = or_panic!(UnixStream::connect(path));
+                or_panic!(stream.write_all(&[i]));
+                let mut buf = [0; 1];
+                or_panic!(stream.read_exact(&mut buf));
+                assert_eq!(buf[0], i * 2);
             }
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&path));
+        let fd = listener.as_raw_fd();
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let server = thread::spawn(move || {
+            listener.accept_loop(move |stream| {
+                server_count.fetch_add(1, Ordering::SeqCst);
+                handle(stream);
+            })
+        });
+        run_clients(&path);
+        assert_eq!(unsafe { libc::shutdown(fd, libc::SHUT_RDWR) }, 0);
+        server.join().unwrap();
+
+        let path = dir.path().join("sock2");
+        let listener = or_panic!(UnixListener::bind(&path));
+        let err = or_panic!(listener.try_clone()).accept_loop_threads(0, handle);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let fd = listener.as_raw_fd();
+        let server_count = count.clone();
+        let server = thread::spawn(move || {
+            listener.accept_loop_threads(4, move |stream| {
+                server_count.fetch_add(1, Ordering::SeqCst);
+                handle(stream);
+            })
         });
+        run_clients(&path);
+        assert_eq!(count.load(Ordering::SeqCst), 100);
+        assert_eq!(unsafe { libc::shutdown(fd, libc::SHUT_RDWR) }, 0);
+        server.join().unwrap();
+    }
 
-        for _ in 0..2 {
-            let mut stream = or_panic!(UnixStream::connect(&socket_path));
-            or_panic!(stream.write_all(&[0]));
-        }
+    #[test]
+    fn read_exact_timeout() {
+        use std::time::Instant;
 
-        thread.join().unwrap();
+        let (s1, mut s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(s2.write_all(b"hello"));
+        or_panic!(s2.write_all(b"world"));
+        let mut buf = [0; 10];
+        or_panic!(s1.read_exact_timeout(&mut buf, Duration::from_millis(500)));
+        assert_eq!(&buf, b"helloworld");
+
+        or_panic!(s2.write_all(b"hello"));
+        let start = Instant::now();
+        let err = s1.read_exact_timeout(&mut buf, Duration::from_millis(500)).unwrap_err();
+        let elapsed = start.elapsed();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("5 of 10"), "{}", err);
+        assert!(elapsed >= Duration::from_millis(500), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+        assert_eq!(or_panic!(s1.read_deadline()), None);
     }
 
     #[test]
-    fn long_path() {
-        let dir = or_panic!(TempDir::new("unix_socket"));
-        let socket_path = dir.path().join("asdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasdfasd\
-                                           fasdfasasdfasdfasdasdfasdfasdfadfasdfasdfasdfasdfasdf");
-        match UnixStream::connect(&socket_path) {
-            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
-            Err(e) => panic!("unexpected error {}", e),
-            Ok(_) => panic!("unexpected success"),
-        }
+    fn long_path_message() {
+        let path = Path::new("/tmp").join(vec!["a"; SUN_PATH_MAX].concat());
+        let len = path.as_os_str().len();
+        let err = UnixListener::bind(&path).unwrap_err().to_string();
+        assert!(err.contains(&path.display().to_string()), "{}", err);
+        assert!(err.contains(&format!("{} bytes", len)), "{}", err);
+        assert!(err.contains(&format!("limit is {}", SUN_PATH_MAX - 1)), "{}", err);
+
+        let name = vec![0; SUN_PATH_MAX + 1];
+        let err = AbstractPath::new(&name).unwrap_err().to_string();
+        assert!(err.contains(&format!("{} bytes", SUN_PATH_MAX + 1)), "{}", err);
+        assert!(err.contains(&format!("limit is {}", SUN_PATH_MAX)), "{}", err);
+    }
 
-        match UnixListener::bind(&socket_path) {
-            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => {}
-            Err(e) => panic!("unexpected error {}", e),
-            Ok(_) => panic!("unexpected success"),
-        }
+    #[test]
+    fn deadlines() {
+        use std::time::Instant;
+
+        let (mut s1, _s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(None, or_panic!(s1.read_deadline()));
+        assert_eq!(None, or_panic!(s1.write_deadline()));
+
+        let deadline = Instant::now() + Duration::from_millis(200);
+        or_panic!(s1.set_read_deadline(Some(deadline)));
+        or_panic!(s1.set_write_deadline(Some(deadline + Duration::from_secs(10))));
+        let read_deadline = or_panic!(s1.read_deadline()).unwrap();
+        assert!(read_deadline > deadline - Duration::from_millis(50));
+        assert!(read_deadline < deadline + Duration::from_millis(50));
+        let write_deadline = or_panic!(s1.write_deadline()).unwrap();
+        assert!(write_deadline > deadline + Duration::from_secs(9));
+
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        let mut buf = [0; 10];
+        let kind = s1.read(&mut buf).expect_err("expected error").kind();
+        assert!(kind == io::ErrorKind::WouldBlock || kind == io::ErrorKind::TimedOut);
+        let wait = start.elapsed();
+        assert!(wait > Duration::from_millis(100), "{:?}", wait);
+        assert!(wait < Duration::from_millis(1000), "{:?}", wait);
+
+        // a deadline which has passed still results in a timeout
+        or_panic!(s1.set_read_deadline(Some(Instant::now() - Duration::from_millis(10))));
+        assert!(or_panic!(s1.read_deadline()).is_some());
+        let start = Instant::now();
+        let kind = s1.read(&mut buf).expect_err("expected error").kind();
+        assert!(kind == io::ErrorKind::WouldBlock || kind == io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        or_panic!(s1.set_read_deadline(None));
+        assert_eq!(None, or_panic!(s1.read_deadline()));
     }
 
     #[test]
@@ -1037,4 +8919,261 @@ mod test {
         or_panic!(sock2.recv_from(&mut buf));
         assert_eq!(msg, &buf[..]);
     }
+
+    #[test]
+    fn test_unbound_datagram() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path));
+        let sock2 = or_panic!(UnixDatagram::unbound());
+
+        match or_panic!(sock2.local_addr()).address() {
+            AddressKind::Unnamed => {}
+            addr => panic!("unexpected address {:?}", addr),
+        }
+
+        let msg = b"hello world";
+        or_panic!(sock2.send_to(msg, &path));
+        let mut buf = [0; 11];
+        or_panic!(sock1.recv_from(&mut buf));
+        assert_eq!(msg, &buf[..]);
+    }
+
+    #[test]
+    fn datagram_recv_connected() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        assert!(!or_panic!(sock1.is_connected()));
+
+        or_panic!(sock1.connect(&path2));
+        or_panic!(sock2.connect(&path1));
+        assert!(or_panic!(sock1.is_connected()));
+
+        or_panic!(sock1.send(b"hello"));
+        or_panic!(sock2.send(b"world"));
+        let mut buf = [0; 10];
+        assert_eq!(or_panic!(sock2.recv(&mut buf)), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(or_panic!(sock1.recv(&mut buf)), 5);
+        assert_eq!(&buf[..5], b"world");
+
+        let (sock1, _sock2) = or_panic!(UnixDatagram::pair());
+        assert!(or_panic!(sock1.is_connected()));
+    }
+
+    #[test]
+    fn seqpacket_recv_msg() {
+        let (s1, s2) = or_panic!(UnixSeqpacket::pair());
+        or_panic!(s1.send(b"hello"));
+        or_panic!(s1.send(b"hello world"));
+
+        let mut buf = [0; 8];
+        assert_eq!(or_panic!(s2.recv_msg(&mut buf)), RecvMsg::Message(5));
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(or_panic!(s2.recv_msg(&mut buf)), RecvMsg::Truncated(8));
+        assert_eq!(&buf, b"hello wo");
+
+        or_panic!(s1.shutdown(::std::net::Shutdown::Write));
+        assert_eq!(or_panic!(s2.recv_msg(&mut buf)), RecvMsg::Eof);
+    }
+
+    #[test]
+    fn cmsg_iter() {
+        let mut data = AncillaryData::new();
+        data.push(libc::SOL_SOCKET, 1234, b"hello");
+        data.push(5678, 4321, b"world!");
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_control = data.buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = data.len as _;
+        let cmsgs = unsafe { CmsgIter::new(&msg) }.collect::<Vec<_>>();
+        assert_eq!(cmsgs,
+                   vec![(libc::SOL_SOCKET, 1234, &b"hello"[..]), (5678, 4321, &b"world!"[..])]);
+
+        let msg: libc::msghdr = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { CmsgIter::new(&msg) }.count(), 0);
+    }
+
+    #[test]
+    fn cmsg_space() {
+        let fd_size = mem::size_of::<RawFd>();
+        for &n in &[0, 1, 3, 253] {
+            let expected = unsafe { libc::CMSG_SPACE((n * fd_size) as libc::c_uint) as usize };
+            assert_eq!(cmsg_space_for_fds(n), expected);
+            assert_eq!(cmsg_space!(n, RawFd), expected);
+        }
+        const SPACE: usize = cmsg_space!(3, RawFd);
+        assert_eq!(SPACE, ::cmsg_space(3 * fd_size));
+
+        // packing the declared number of fds fills the space exactly
+        let fds = [0, 1, 2];
+        let mut data = AncillaryData::new();
+        or_panic!(data.add_fds(&fds));
+        assert_eq!(data.len, cmsg_space_for_fds(fds.len()));
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let expected = unsafe {
+                libc::CMSG_SPACE(mem::size_of::<libc::ucred>() as libc::c_uint) as usize
+            };
+            assert_eq!(::cmsg_space_for_creds(), expected);
+        }
+    }
+
+    #[test]
+    fn datagram_recv_from_deadline() {
+        use std::time::Instant;
+
+        let (s1, s2) = or_panic!(UnixDatagram::pair());
+        or_panic!(s1.send(b"hello"));
+        let mut buf = [0; 10];
+        let (count, _) = or_panic!(s2.recv_from_deadline(&mut buf,
+                                                         Instant::now() +
+                                                         Duration::from_secs(10)));
+        assert_eq!(&buf[..count], b"hello");
+
+        let start = Instant::now();
+        let deadline = start.checked_sub(Duration::from_millis(200)).unwrap_or(start);
+        let err = s2.recv_from_deadline(&mut buf, deadline).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let start = Instant::now();
+        let err = s2.recv_from_deadline(&mut buf, start + Duration::from_millis(50)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(or_panic!(s2.inner.timeout(libc::SO_RCVTIMEO)), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn incoming_filter() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use {cvt, sockaddr_un, Inner};
+
+        fn connect_from(name: &[u8], path: &Path) -> UnixStream {
+            let inner = or_panic!(Inner::new(libc::SOCK_STREAM));
+            unsafe {
+                let (addr, len) = or_panic!(sockaddr_un(OsStr::from_bytes(name)));
+                or_panic!(cvt(libc::bind(inner.0, &addr as *const _ as *const _, len)));
+                let (addr, len) = or_panic!(sockaddr_un(path));
+                or_panic!(cvt(libc::connect(inner.0, &addr as *const _ as *const _, len)));
+            }
+            UnixStream { inner: inner }
+        }
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let listener = or_panic!(UnixListener::bind(&path));
+
+        let good = format!("unix_socket_filter_{}_good", std::process::id());
+        let bad = format!("unix_socket_filter_{}_bad", std::process::id());
+        let mut rejected = connect_from(format!("\0{}", bad).as_bytes(), &path);
+        let _accepted = connect_from(format!("\0{}", good).as_bytes(), &path);
+        let _unnamed = or_panic!(UnixStream::connect(&path));
+
+        let mut incoming = listener.incoming_filter(|addr| {
+            addr.as_abstract_bytes().is_some_and(|name| name.ends_with(b"good"))
+        });
+        let stream = or_panic!(incoming.next().unwrap());
+        assert_eq!(or_panic!(stream.peer_addr()).as_abstract_bytes(),
+                   Some(good.as_bytes()));
+
+        let mut buf = [0; 1];
+        assert_eq!(or_panic!(rejected.read(&mut buf)), 0);
+
+        let mut incoming = listener.incoming_filter(|addr| addr.address().is_unnamed());
+        let stream = or_panic!(incoming.next().unwrap());
+        assert!(or_panic!(stream.peer_addr()).address().is_unnamed());
+    }
+
+    #[test]
+    fn buf_stream_lines() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            for i in 0..10 {
+                or_panic!(write!(s1, "{} {}\n", i, i * i));
+            }
+        });
+
+        let stream = BufUnixStream::with_capacities(s2, 16, 16);
+        assert!(or_panic!(stream.peer_addr()).address().is_unnamed());
+        let squares = stream.lines()
+                            .map(|line| {
+                                let line = or_panic!(line);
+                                let mut parts = line.split(' ').map(|p| p.parse::<u32>().unwrap());
+                                (parts.next().unwrap(), parts.next().unwrap())
+                            })
+                            .collect::<Vec<_>>();
+        assert_eq!(squares, (0..10).map(|i| (i, i * i)).collect::<Vec<_>>());
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn datagram_recv_from_opt() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        let unbound = or_panic!(UnixDatagram::unbound());
+
+        let mut buf = [0; 11];
+        or_panic!(unbound.send_to(b"hello", &path1));
+        let (count, addr) = or_panic!(sock1.recv_from_opt(&mut buf));
+        assert_eq!(&buf[..count], b"hello");
+        assert_eq!(addr, None);
+
+        or_panic!(sock2.send_to(b"world", &path1));
+        let (count, addr) = or_panic!(sock1.recv_from_opt(&mut buf));
+        assert_eq!(&buf[..count], b"world");
+        assert_eq!(addr.and_then(|a| a.as_os_str().map(|p| p.to_owned())),
+                   Some(path2.into_os_string()));
+    }
+
+    #[test]
+    fn test_connect_unix_datagram() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path1 = dir.path().join("sock1");
+        let path2 = dir.path().join("sock2");
+        let path3 = dir.path().join("sock3");
+
+        let sock1 = or_panic!(UnixDatagram::bind(&path1));
+        let sock2 = or_panic!(UnixDatagram::bind(&path2));
+        let sock3 = or_panic!(UnixDatagram::bind(&path3));
+
+        match sock2.send(b"hello") {
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOTCONN) => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpected success"),
+        }
+
+        or_panic!(sock2.connect(&path3));
+        or_panic!(sock2.connect(&path1));
+        match or_panic!(sock2.peer_addr()).address() {
+            AddressKind::Pathname(path) => assert_eq!(path1, path),
+            addr => panic!("unexpected address {:?}", addr),
+        }
+
+        // datagrams from anyone other than the peer are not received
+        let _ = sock3.send_to(b"ignored", &path2);
+
+        let msg = b"hello world";
+        or_panic!(sock1.send_to(msg, &path2));
+        let mut buf = [0; 11];
+        assert_eq!(11, or_panic!(sock2.recv(&mut buf)));
+        assert_eq!(msg, &buf[..]);
+
+        or_panic!(sock2.send(msg));
+        let mut buf = [0; 11];
+        or_panic!(sock1.recv_from(&mut buf));
+        assert_eq!(msg, &buf[..]);
+    }
 }
@@ -0,0 +1,622 @@
+//! Linux-specific extensions.
+//!
+//! The Linux-only methods of `UnixStream` and `UnixDatagram` are provided by
+//! the extension traits in this module, so that the methods of the main
+//! socket types remain portable. Linux-only settings of the builder types,
+//! such as `DatagramOptions::passcred`, and methods with a fallback on other
+//! platforms, such as `UnixDatagram::recv_many`, remain on those types.
+//!
+//! The minimum kernel version for each Linux-specific feature of this crate
+//! is:
+//!
+//! | Feature | Methods | Kernel |
+//! |---|---|---|
+//! | Abstract namespace | `bind_abstract`, `connect_abstract` | 2.2 |
+//! | `SO_PEERCRED` | `UnixStream::peer_cred` | 2.2 |
+//! | `SO_PASSCRED` and `SCM_CREDENTIALS` | `set_passcred`, `send_cred` | 2.2 |
+//! | `SO_PRIORITY` | `set_priority`, `priority` | 2.2 |
+//! | `TIOCOUTQ` | `outgoing_queued_bytes` | 2.2 |
+//! | `sendfile` | `send_file` | 2.2 |
+//! | `splice` | `splice_from`, `splice_to`, `splice_to_datagram` | 2.6.17 |
+//! | `SO_TIMESTAMPNS` | `set_timestamp_ns`, `recv_from_timestamp_ns` | 2.6.22 |
+//! | `SO_MARK` | `set_mark`, `mark` | 2.6.25 |
+//! | `SOCK_CLOEXEC` and `accept4` | `bind_cloexec`, `accept` | 2.6.28 |
+//! | `recvmmsg` | `UnixDatagram::recv_many` | 2.6.33 |
+//! | `sendmmsg` | `UnixDatagram::send_many` | 3.0 |
+//!
+//! Only supported on Linux and Android.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::ptr;
+use std::time::SystemTime;
+
+use libc;
+
+use {AbstractPathBuf, AncillaryData, Inner, SocketAddr, UCred, UnixDatagram, UnixListener,
+     UnixStream, cvt, cvt_s, sockaddr_un};
+
+/// Creates a `UnixListener` bound to the abstract address named by `name`.
+///
+/// `name` should not include the leading null byte, which is added here. This
+/// is a shorthand for `UnixListener::bind_abstract` with an
+/// `AbstractPathBuf`, and fails in the same way if `name` is invalid.
+pub fn bind_abstract(name: &[u8]) -> io::Result<UnixListener> {
+    UnixListener::bind_abstract(&try!(AbstractPathBuf::new(name)))
+}
+
+/// Connects to the abstract address named by `name`.
+///
+/// As with `bind_abstract`, `name` should not include the leading null byte.
+pub fn connect_abstract(name: &[u8]) -> io::Result<UnixStream> {
+    UnixStream::connect_abstract(&try!(AbstractPathBuf::new(name)))
+}
+
+/// Linux-specific extensions to `UnixStream`.
+pub trait LinuxUnixStreamExt {
+    /// Sends data on the socket along with an `SCM_CREDENTIALS` control
+    /// message carrying `cred`.
+    ///
+    /// The kernel will reject credentials which do not match the sending
+    /// process unless it is privileged. The receiver must have enabled
+    /// `SO_PASSCRED` to be able to read them.
+    fn send_cred(&self, buf: &[u8], cred: &UCred) -> io::Result<usize>;
+
+    /// Enables or disables the `SO_PASSCRED` option.
+    ///
+    /// When enabled, the credentials of the sending process are attached to
+    /// received data as an `SCM_CREDENTIALS` control message, which can be
+    /// read with `recv_with_ancillary`.
+    fn set_passcred(&self, passcred: bool) -> io::Result<()>;
+
+    /// Returns the value of the `SO_PASSCRED` option.
+    fn passcred(&self) -> io::Result<bool>;
+
+    /// Sets the value of the `SO_MARK` option, which tags packets for
+    /// routing and filtering.
+    ///
+    /// Setting the mark requires the `CAP_NET_ADMIN` capability, or on newer
+    /// kernels `CAP_NET_RAW`, and fails with `EPERM` otherwise.
+    fn set_mark(&self, mark: u32) -> io::Result<()>;
+
+    /// Returns the value of the `SO_MARK` option.
+    fn mark(&self) -> io::Result<u32>;
+
+    /// Sets the value of the `SO_PRIORITY` option.
+    ///
+    /// Priorities outside the range 0 to 6 require the `CAP_NET_ADMIN`
+    /// capability, and fail with `EPERM` otherwise.
+    fn set_priority(&self, priority: i32) -> io::Result<()>;
+
+    /// Returns the value of the `SO_PRIORITY` option.
+    fn priority(&self) -> io::Result<i32>;
+
+    /// Returns the amount of sent data which the peer has not yet read,
+    /// using the `TIOCOUTQ` ioctl.
+    ///
+    /// Linux includes bookkeeping overhead in this count, so it will
+    /// generally be larger than the number of bytes written.
+    fn outgoing_queued_bytes(&self) -> io::Result<usize>;
+
+    /// Moves up to `len` bytes from `from_fd` into the socket with
+    /// `splice(2)`, without copying them through userspace.
+    ///
+    /// `splice` requires one side of the transfer to be a pipe, so `from_fd`
+    /// must refer to one. If `offset` is provided, data is read from that
+    /// position and the offset is advanced; it must be `None` for pipes and
+    /// other unseekable descriptors. An `InvalidInput` error is returned if
+    /// `offset` does not fit in the platform's `loff_t`. `flags` is a
+    /// combination of the `SPLICE_F_*` flags.
+    ///
+    /// On success, returns the number of bytes moved.
+    fn splice_from(&self,
+                   from_fd: RawFd,
+                   offset: Option<&mut u64>,
+                   len: usize,
+                   flags: u32)
+                   -> io::Result<usize>;
+
+    /// Moves up to `len` bytes from the socket into `to_fd` with `splice(2)`,
+    /// without copying them through userspace.
+    ///
+    /// As with `splice_from`, `to_fd` must refer to a pipe.
+    ///
+    /// On success, returns the number of bytes moved.
+    fn splice_to(&self,
+                 to_fd: RawFd,
+                 offset: Option<&mut u64>,
+                 len: usize,
+                 flags: u32)
+                 -> io::Result<usize>;
+
+    /// Moves up to `count` bytes from the socket to the peer of `udg` with
+    /// `splice(2)`, without copying them through userspace.
+    ///
+    /// `udg` must be connected. The data passes through a temporary pipe, so
+    /// at most the pipe's capacity, normally 64 KiB, is moved per call. The
+    /// data is not guaranteed to arrive as a single datagram: depending on
+    /// the kernel, each page-sized pipe buffer may be sent as a datagram of
+    /// its own. If sending to `udg` fails after data has been read from the
+    /// socket, that data is lost.
+    ///
+    /// On success, returns the number of bytes moved.
+    fn splice_to_datagram(&self, udg: &UnixDatagram, count: usize) -> io::Result<usize>;
+
+    /// Sends up to `count` bytes of `file`, starting at `offset`, with
+    /// `sendfile(2)`.
+    ///
+    /// The data is copied within the kernel, and the file's own position is
+    /// not changed. An `InvalidInput` error is returned if `offset` does not
+    /// fit in the platform's `off_t`.
+    ///
+    /// On success, returns the number of bytes sent.
+    fn send_file(&self, file: &fs::File, offset: u64, count: usize) -> io::Result<usize>;
+}
+
+/// Linux-specific extensions to `UnixDatagram`.
+pub trait LinuxUnixDatagramExt {
+    /// Sends data on the socket to the given address along with an
+    /// `SCM_CREDENTIALS` control message carrying `cred`.
+    ///
+    /// The kernel will reject credentials which do not match the sending
+    /// process unless it is privileged. The receiver must have enabled
+    /// `SO_PASSCRED` to be able to read them.
+    fn send_to_with_cred<P: AsRef<Path>>(&self,
+                                         buf: &[u8],
+                                         path: P,
+                                         cred: &UCred)
+                                         -> io::Result<usize>;
+
+    /// Enables or disables the `SO_PASSCRED` option.
+    ///
+    /// When enabled, the credentials of the sending process are attached to
+    /// received data as an `SCM_CREDENTIALS` control message, which can be
+    /// read with `recv_with_ancillary`.
+    fn set_passcred(&self, passcred: bool) -> io::Result<()>;
+
+    /// Returns the value of the `SO_PASSCRED` option.
+    fn passcred(&self) -> io::Result<bool>;
+
+    /// Sets the value of the `SO_MARK` option, which tags packets for
+    /// routing and filtering.
+    ///
+    /// Setting the mark requires the `CAP_NET_ADMIN` capability, or on newer
+    /// kernels `CAP_NET_RAW`, and fails with `EPERM` otherwise.
+    fn set_mark(&self, mark: u32) -> io::Result<()>;
+
+    /// Returns the value of the `SO_MARK` option.
+    fn mark(&self) -> io::Result<u32>;
+
+    /// Sets the value of the `SO_PRIORITY` option.
+    ///
+    /// Priorities outside the range 0 to 6 require the `CAP_NET_ADMIN`
+    /// capability, and fail with `EPERM` otherwise.
+    fn set_priority(&self, priority: i32) -> io::Result<()>;
+
+    /// Returns the value of the `SO_PRIORITY` option.
+    fn priority(&self) -> io::Result<i32>;
+
+    /// Enables or disables the `SO_TIMESTAMPNS` option.
+    ///
+    /// This is like `set_timestamp`, but the timestamp reported by
+    /// `recv_from_timestamp` has nanosecond rather than microsecond
+    /// resolution.
+    fn set_timestamp_ns(&self, timestamp: bool) -> io::Result<()>;
+
+    /// Receives data from the socket along with the nanosecond resolution
+    /// time at which the kernel received it.
+    ///
+    /// This is the same as `recv_from_timestamp`, which reports either kind
+    /// of timestamp, and requires `set_timestamp_ns` to have been enabled.
+    fn recv_from_timestamp_ns(&self,
+                              buf: &mut [u8])
+                              -> io::Result<(usize, SocketAddr, Option<SystemTime>)>;
+}
+
+impl LinuxUnixStreamExt for UnixStream {
+    fn send_cred(&self, buf: &[u8], cred: &UCred) -> io::Result<usize> {
+        let mut ancillary = AncillaryData::new();
+        ancillary.add_credentials(cred);
+        self.send_with_ancillary(buf, &ancillary)
+    }
+
+    fn set_passcred(&self, passcred: bool) -> io::Result<()> {
+        self.inner.set_passcred(passcred)
+    }
+
+    fn passcred(&self) -> io::Result<bool> {
+        self.inner.passcred()
+    }
+
+    fn set_mark(&self, mark: u32) -> io::Result<()> {
+        self.inner.set_mark(mark)
+    }
+
+    fn mark(&self) -> io::Result<u32> {
+        self.inner.mark()
+    }
+
+    fn set_priority(&self, priority: i32) -> io::Result<()> {
+        self.inner.set_priority(priority)
+    }
+
+    fn priority(&self) -> io::Result<i32> {
+        self.inner.priority()
+    }
+
+    fn outgoing_queued_bytes(&self) -> io::Result<usize> {
+        self.inner.outgoing_queued_bytes()
+    }
+
+    fn splice_from(&self,
+                   from_fd: RawFd,
+                   offset: Option<&mut u64>,
+                   len: usize,
+                   flags: u32)
+                   -> io::Result<usize> {
+        splice(from_fd, offset, self.inner.0, None, len, flags)
+    }
+
+    fn splice_to(&self,
+                 to_fd: RawFd,
+                 offset: Option<&mut u64>,
+                 len: usize,
+                 flags: u32)
+                 -> io::Result<usize> {
+        splice(self.inner.0, None, to_fd, offset, len, flags)
+    }
+
+    fn splice_to_datagram(&self, udg: &UnixDatagram, count: usize) -> io::Result<usize> {
+        let mut fds = [0; 2];
+        try!(cvt(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }));
+        let (pipe_rd, pipe_wr) = (Inner(fds[0]), Inner(fds[1]));
+
+        let moved = try!(splice(self.inner.0, None, pipe_wr.0, None, count, 0));
+        let mut sent = 0;
+        while sent < moved {
+            sent += try!(splice(pipe_rd.0, None, udg.inner.0, None, moved - sent, 0));
+        }
+        Ok(moved)
+    }
+
+    fn send_file(&self, file: &fs::File, offset: u64, count: usize) -> io::Result<usize> {
+        let mut offset = try!(libc::off_t::try_from(offset).map_err(|_| offset_error()));
+        unsafe {
+            let count = try!(cvt_s(libc::sendfile(self.inner.0, file.as_raw_fd(), &mut offset,
+                                                  count)));
+            Ok(count as usize)
+        }
+    }
+}
+
+impl LinuxUnixDatagramExt for UnixDatagram {
+    fn send_to_with_cred<P: AsRef<Path>>(&self,
+                                         buf: &[u8],
+                                         path: P,
+                                         cred: &UCred)
+                                         -> io::Result<usize> {
+        let mut ancillary = AncillaryData::new();
+        ancillary.add_credentials(cred);
+        unsafe {
+            let (addr, len) = try!(sockaddr_un(path));
+            self.inner.send_msg_to(&[io::IoSlice::new(buf)], Some((&addr, len)), &ancillary, 0)
+        }
+    }
+
+    fn set_passcred(&self, passcred: bool) -> io::Result<()> {
+        self.inner.set_passcred(passcred)
+    }
+
+    fn passcred(&self) -> io::Result<bool> {
+        self.inner.passcred()
+    }
+
+    fn set_mark(&self, mark: u32) -> io::Result<()> {
+        self.inner.set_mark(mark)
+    }
+
+    fn mark(&self) -> io::Result<u32> {
+        self.inner.mark()
+    }
+
+    fn set_priority(&self, priority: i32) -> io::Result<()> {
+        self.inner.set_priority(priority)
+    }
+
+    fn priority(&self) -> io::Result<i32> {
+        self.inner.priority()
+    }
+
+    fn set_timestamp_ns(&self, timestamp: bool) -> io::Result<()> {
+        self.inner.setsockopt(libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, timestamp as libc::c_int)
+    }
+
+    fn recv_from_timestamp_ns(&self,
+                              buf: &mut [u8])
+                              -> io::Result<(usize, SocketAddr, Option<SystemTime>)> {
+        self.recv_from_timestamp(buf)
+    }
+}
+
+fn offset_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "offset is too large for the platform")
+}
+
+fn splice(fd_in: RawFd,
+          off_in: Option<&mut u64>,
+          fd_out: RawFd,
+          off_out: Option<&mut u64>,
+          len: usize,
+          flags: u32)
+          -> io::Result<usize> {
+    fn as_ptr(off: &mut Option<libc::loff_t>) -> *mut libc::loff_t {
+        off.as_mut().map_or(ptr::null_mut(), |off| off as *mut _)
+    }
+
+    fn to_loff(off: &Option<&mut u64>) -> io::Result<Option<libc::loff_t>> {
+        match *off {
+            Some(ref off) => libc::loff_t::try_from(**off).map(Some).map_err(|_| offset_error()),
+            None => Ok(None),
+        }
+    }
+
+    let mut loff_in = try!(to_loff(&off_in));
+    let mut loff_out = try!(to_loff(&off_out));
+    let count = unsafe {
+        try!(cvt_s(libc::splice(fd_in,
+                                as_ptr(&mut loff_in),
+                                fd_out,
+                                as_ptr(&mut loff_out),
+                                len,
+                                flags as libc::c_uint)))
+    };
+
+    if let (Some(off), Some(loff)) = (off_in, loff_in) {
+        *off = loff as u64;
+    }
+    if let (Some(off), Some(loff)) = (off_out, loff_out) {
+        *off = loff as u64;
+    }
+    Ok(count as usize)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::os::unix::io::FromRawFd;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+    use self::tempdir::TempDir;
+
+    use {AddressKind, AncillaryMessage, CmsgEntry, UnixDatagram, UnixStream, UCred};
+    use super::*;
+
+    macro_rules! or_panic {
+        ($e:expr) => {
+            match $e {
+                Ok(e) => e,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn passcred() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        assert!(!or_panic!(s2.passcred()));
+        or_panic!(s2.set_passcred(true));
+        assert!(or_panic!(s2.passcred()));
+
+        or_panic!(s1.write_all(b"hello"));
+        let mut msg = AncillaryMessage::with_capacity(1024);
+        let mut buf = [0; 5];
+        or_panic!(s2.recv_with_ancillary(&mut buf, &mut msg));
+        let cred = UCred {
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            pid: unsafe { libc::getpid() },
+        };
+        assert_eq!(vec![CmsgEntry::Credentials(cred)], msg.entries().collect::<Vec<_>>());
+
+        or_panic!(s2.set_passcred(false));
+        assert!(!or_panic!(s2.passcred()));
+
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d2.set_passcred(true));
+        assert!(or_panic!(d2.passcred()));
+        or_panic!(d1.send(b"hello"));
+        or_panic!(d2.recv_with_ancillary(&mut buf, &mut msg));
+        match msg.entries().next() {
+            Some(CmsgEntry::Credentials(_)) => {}
+            entry => panic!("unexpected entry {:?}", entry),
+        }
+    }
+
+    #[test]
+    fn send_cred() {
+        let cred = UCred {
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            pid: unsafe { libc::getpid() },
+        };
+        let mut msg = AncillaryMessage::with_capacity(1024);
+        let mut buf = [0; 5];
+
+        let (s1, s2) = or_panic!(UnixStream::unnamed());
+        or_panic!(s2.set_passcred(true));
+        or_panic!(s1.send_cred(b"hello", &cred));
+        or_panic!(s2.recv_with_ancillary(&mut buf, &mut msg));
+        assert_eq!(b"hello", &buf);
+        assert_eq!(vec![CmsgEntry::Credentials(cred.clone())], msg.entries().collect::<Vec<_>>());
+
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("sock");
+        let receiver = or_panic!(UnixDatagram::bind(&path));
+        or_panic!(receiver.set_passcred(true));
+        let sender = or_panic!(UnixDatagram::unbound());
+        or_panic!(sender.send_to_with_cred(b"hello", &path, &cred));
+        or_panic!(receiver.recv_with_ancillary(&mut buf, &mut msg));
+        assert_eq!(b"hello", &buf);
+        assert_eq!(vec![CmsgEntry::Credentials(cred)], msg.entries().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn splice() {
+        let dir = or_panic!(TempDir::new("unix_socket"));
+        let path = dir.path().join("file");
+        let data = (0..100000).map(|i| i as u8).collect::<Vec<_>>();
+        or_panic!(or_panic!(File::create(&path)).write_all(&data));
+        let file = or_panic!(File::open(&path));
+
+        let (s1, mut s2) = or_panic!(UnixStream::unnamed());
+        let thread = thread::spawn(move || {
+            let mut offset = 0;
+            while offset < data.len() {
+                offset += or_panic!(s1.send_file(&file, offset as u64, data.len() - offset));
+            }
+            (s1, file, data)
+        });
+        let mut buf = vec![0; 100000];
+        or_panic!(s2.read_exact(&mut buf));
+        let (s1, file, data) = thread.join().unwrap();
+        assert!(data == buf);
+        let err = s1.send_file(&file, u64::max_value(), 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let mut fds = [0; 2];
+        assert_eq!(0, unsafe { libc::pipe(fds.as_mut_ptr()) });
+        let (reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+        or_panic!((&writer).write_all(b"hello"));
+        assert_eq!(5, or_panic!(s1.splice_from(reader.as_raw_fd(), None, 5, 0)));
+        assert_eq!(5, or_panic!(s2.splice_to(writer.as_raw_fd(), None, 5, 0)));
+        let mut buf = [0; 5];
+        or_panic!((&reader).read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        let mut offset = 0;
+        match s1.splice_from(reader.as_raw_fd(), Some(&mut offset), 5, 0) {
+            Err(ref e) if e.raw_os_error() == Some(libc::ESPIPE) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let mut offset = u64::max_value();
+        let err = s1.splice_from(file.as_raw_fd(), Some(&mut offset), 5, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = s2.splice_to(writer.as_raw_fd(), Some(&mut offset), 5, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn splice_to_datagram() {
+        let (mut s1, s2) = or_panic!(UnixStream::unnamed());
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+
+        let data = (0..200).map(|i| i as u8).collect::<Vec<_>>();
+        or_panic!(s1.write_all(&data));
+        assert_eq!(or_panic!(s2.splice_to_datagram(&d1, 1000)), data.len());
+
+        let mut buf = [0; 1000];
+        let count = or_panic!(d2.recv(&mut buf));
+        assert_eq!(&buf[..count], &data[..]);
+
+        // more than a page, so the data may be split across datagrams; the
+        // receiver runs concurrently so that the sender never fills its queue
+        let data = (0..100000).map(|i| i as u8).collect::<Vec<_>>();
+        let thread = thread::spawn(move || {
+            let mut received = vec![];
+            let mut buf = vec![0; 128 * 1024];
+            loop {
+                match or_panic!(d2.recv(&mut buf)) {
+                    0 => return received,
+                    count => received.extend_from_slice(&buf[..count]),
+                }
+            }
+        });
+        or_panic!(s1.write_all(&data[..70000]));
+        let moved = or_panic!(s2.splice_to_datagram(&d1, data.len()));
+        assert!(moved > 4096 && moved <= 70000, "{}", moved);
+        or_panic!(d1.send(b""));
+        let received = thread.join().unwrap();
+        assert_eq!(received.len(), moved);
+        assert_eq!(&received[..], &data[..moved]);
+    }
+
+    #[test]
+    fn linux_abstract_names() {
+        let name = format!("unix_socket linux_abstract_names {}", std::process::id());
+        let name = name.as_bytes();
+        let listener = or_panic!(bind_abstract(name));
+        assert_eq!(or_panic!(listener.local_addr()).address(), AddressKind::Abstract(name));
+
+        let mut s1 = or_panic!(connect_abstract(name));
+        let mut s2 = or_panic!(listener.accept());
+        or_panic!(s1.write_all(b"hello"));
+        let mut buf = [0; 5];
+        or_panic!(s2.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        assert!(bind_abstract(&[b'a'; 200]).is_err());
+        let missing = format!("unix_socket missing abstract name {}", std::process::id());
+        assert!(connect_abstract(missing.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn linux_priority() {
+        let root = unsafe { libc::geteuid() } == 0;
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        assert_eq!(or_panic!(s1.priority()), 0);
+        or_panic!(s1.set_priority(5));
+        assert_eq!(or_panic!(s1.priority()), 5);
+        match s1.set_priority(7) {
+            Ok(()) => assert_eq!(or_panic!(s1.priority()), 7),
+            Err(ref e) if !root && e.raw_os_error() == Some(libc::EPERM) => {}
+            Err(e) => panic!("unexpected error {}", e),
+        }
+
+        let (d1, _d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d1.set_priority(5));
+        assert_eq!(or_panic!(d1.priority()), 5);
+    }
+
+    #[test]
+    fn linux_mark() {
+        let root = unsafe { libc::geteuid() } == 0;
+        let (s1, _s2) = or_panic!(UnixStream::unnamed());
+        match s1.set_mark(42) {
+            Ok(()) => assert_eq!(or_panic!(s1.mark()), 42),
+            Err(ref e) if !root && e.raw_os_error() == Some(libc::EPERM) => {}
+            Err(e) => panic!("unexpected error {}", e),
+        }
+
+        let (d1, _d2) = or_panic!(UnixDatagram::pair());
+        match d1.set_mark(42) {
+            Ok(()) => assert_eq!(or_panic!(d1.mark()), 42),
+            Err(ref e) if !root && e.raw_os_error() == Some(libc::EPERM) => {}
+            Err(e) => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn timestamp_ns() {
+        let (d1, d2) = or_panic!(UnixDatagram::pair());
+        or_panic!(d2.set_timestamp_ns(true));
+        or_panic!(d1.send(b"world"));
+        let mut buf = [0; 5];
+        let (count, _, time) = or_panic!(d2.recv_from_timestamp_ns(&mut buf));
+        assert_eq!(5, count);
+        assert_eq!(b"world", &buf);
+
+        let (time, now) = (time.unwrap(), SystemTime::now());
+        let diff = match now.duration_since(time) {
+            Ok(diff) => diff,
+            Err(e) => e.duration(),
+        };
+        assert!(diff < Duration::from_millis(100), "{:?} vs {:?}", time, now);
+    }
+}